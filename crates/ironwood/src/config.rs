@@ -19,6 +19,11 @@ pub struct Config {
     pub bloom_transform: Option<Arc<dyn Fn(PublicKey) -> PublicKey + Send + Sync>>,
     /// Callback invoked when a new path is discovered.
     pub path_notify: Option<Arc<dyn Fn(PublicKey) + Send + Sync>>,
+    /// Callback invoked with the new root and parent when either changes.
+    pub tree_notify: Option<Arc<dyn Fn(PublicKey, PublicKey) + Send + Sync>>,
+    /// Callback invoked when an encrypted session is established (`true`)
+    /// or expires (`false`).
+    pub session_notify: Option<Arc<dyn Fn(PublicKey, bool) + Send + Sync>>,
     /// Timeout before expiring a cached path. Default: 1 minute.
     pub path_timeout: Duration,
     /// Minimum interval between path lookups to the same destination. Default: 1 second.
@@ -35,6 +40,8 @@ impl Default for Config {
             peer_max_message_size: 1024 * 1024,
             bloom_transform: None,
             path_notify: None,
+            tree_notify: None,
+            session_notify: None,
             path_timeout: Duration::from_secs(60),
             path_throttle: Duration::from_secs(1),
         }
@@ -83,6 +90,22 @@ impl Config {
         self
     }
 
+    pub fn with_tree_notify(
+        mut self,
+        f: impl Fn(PublicKey, PublicKey) + Send + Sync + 'static,
+    ) -> Self {
+        self.tree_notify = Some(Arc::new(f));
+        self
+    }
+
+    pub fn with_session_notify(
+        mut self,
+        f: impl Fn(PublicKey, bool) + Send + Sync + 'static,
+    ) -> Self {
+        self.session_notify = Some(Arc::new(f));
+        self
+    }
+
     pub fn with_path_timeout(mut self, d: Duration) -> Self {
         self.path_timeout = d;
         self
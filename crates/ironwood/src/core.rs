@@ -184,6 +184,7 @@ impl crate::types::PacketConn for PacketConnImpl {
         key: Addr,
         conn: Box<dyn AsyncConn>,
         prio: u8,
+        link_id: u64,
     ) -> Result<()> {
         if self.closed.load(Ordering::Relaxed) {
             return Err(Error::Closed);
@@ -206,7 +207,7 @@ impl crate::types::PacketConn for PacketConnImpl {
         // Allocate the peer in the peers manager
         let handle = {
             let mut peers = self.peers.lock().await;
-            peers.allocate_peer(peer_key, prio, writer_tx.clone(), peer_cancel.clone())
+            peers.allocate_peer(peer_key, prio, link_id, writer_tx.clone(), peer_cancel.clone())
         };
 
         let peer_id = handle.id;
@@ -342,13 +343,51 @@ impl crate::types::PacketConn for PacketConnImpl {
     }
 }
 
-/// Public peer info returned by `get_peers()`.
+/// Public peer info returned by `get_peers()`, one per link.
 #[derive(Clone, Debug)]
 pub struct PeerInfo {
     pub key: [u8; 32],
     pub port: u64,
     pub priority: u8,
+    /// Smoothed round-trip time, 0 until measured.
     pub latency_ms: f64,
+    /// Routing cost of the link (latency in milliseconds, at least 1).
+    pub cost: u64,
+    /// Connection order: links to the same key that came up earlier have
+    /// lower values.
+    pub order: u64,
+    /// The id the link was handed to `handle_conn` with.
+    pub link_id: u64,
+    /// Whether this is the link the router uses for traffic to `key`.
+    pub preferred: bool,
+}
+
+/// Packets dropped under backpressure, returned by `queue_drops()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueDrops {
+    /// Dropped from the receive queue because nobody read them in time.
+    pub delivery: u64,
+    /// Dropped from peer send queues because the link could not keep up.
+    pub send: u64,
+}
+
+/// Public path entry returned by `get_paths()`.
+#[derive(Clone, Debug)]
+pub struct PathEntry {
+    pub key: [u8; 32],
+    /// Tree coordinates to the destination, empty while no path is known.
+    pub path: Vec<u64>,
+    /// Sequence number of the destination's last path notification.
+    pub seq: u64,
+    /// Time since the path was last refreshed or, without a path, since
+    /// the lookup started.
+    pub age: Duration,
+    /// Whether we are waiting for the destination to answer a lookup.
+    pub lookup_pending: bool,
+    /// Whether new lookups are held back by `path_throttle`.
+    pub throttled: bool,
+    /// Whether the path was reported broken.
+    pub broken: bool,
 }
 
 /// Public tree entry returned by `get_tree()`.
@@ -360,25 +399,28 @@ pub struct TreeEntry {
 }
 
 impl PacketConnImpl {
-    /// Get info about all connected peers.
+    /// Get info about all connected peers, sorted by key and connection order.
     pub async fn get_peers(&self) -> Vec<PeerInfo> {
         let router = self.router.lock().await;
         let mut result = Vec::new();
         for (key, entries) in &router.peers {
+            let preferred = router.preferred_peer(key);
             for (_id, entry) in entries {
-                let latency_ms = router
-                    .lags
-                    .get(&entry.id)
-                    .map(|d| d.as_secs_f64() * 1000.0)
-                    .unwrap_or(0.0);
                 result.push(PeerInfo {
                     key: *key,
                     port: entry.port,
                     priority: entry.prio,
-                    latency_ms,
+                    latency_ms: router
+                        .latency(entry.id)
+                        .map_or(0.0, |d| d.as_secs_f64() * 1000.0),
+                    cost: router.get_cost(entry.id),
+                    order: entry.order,
+                    link_id: entry.link_id,
+                    preferred: preferred == Some(entry.id),
                 });
             }
         }
+        result.sort_by_key(|p| (p.key, p.order));
         result
     }
 
@@ -397,6 +439,14 @@ impl PacketConnImpl {
         result
     }
 
+    /// Get cached paths and pending lookups.
+    pub async fn get_paths(&self) -> Vec<PathEntry> {
+        let router = self.router.lock().await;
+        router
+            .pathfinder
+            .entries(router.path_throttle, |key| router.blooms.x_key(key, &router.bloom_transform))
+    }
+
     /// Get the number of routing entries (tree nodes known).
     pub async fn routing_entries(&self) -> usize {
         let router = self.router.lock().await;
@@ -410,6 +460,14 @@ impl PacketConnImpl {
         let (_root, path) = router.get_root_and_path(&self_key);
         path
     }
+
+    /// Get the number of packets dropped from full queues so far.
+    pub async fn queue_drops(&self) -> QueueDrops {
+        QueueDrops {
+            delivery: self.delivery_queue.drops(),
+            send: self.peers.lock().await.send_drops.load(Ordering::Relaxed),
+        }
+    }
 }
 
 /// Create a new PacketConn. This is the primary public constructor.
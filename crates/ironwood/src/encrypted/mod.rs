@@ -8,6 +8,7 @@ pub(crate) mod session;
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use ed25519_dalek::SigningKey;
 use tokio::sync::{mpsc, Mutex};
@@ -30,6 +31,26 @@ struct DecryptedMessage {
     data: Vec<u8>,
 }
 
+/// Public session entry returned by `get_sessions()`.
+#[derive(Clone, Debug)]
+pub struct SessionEntry {
+    pub key: [u8; 32],
+    /// False while our init is still waiting for the remote's ack.
+    pub established: bool,
+    /// Whether a packet is held back until the handshake completes.
+    pub buffered: bool,
+    /// Payload bytes received and sent in this session.
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    /// Time since the session (or, if not established, the handshake) started.
+    pub uptime: Duration,
+    /// Time since our keys were last rotated, if they have been.
+    pub since_rekey: Option<Duration>,
+    pub since_activity: Duration,
+    pub local_key_seq: u64,
+    pub remote_key_seq: u64,
+}
+
 /// Encrypted PacketConn: wraps a network `PacketConnImpl` with encryption.
 pub struct EncryptedPacketConn {
     /// The underlying network-level PacketConn.
@@ -57,8 +78,10 @@ impl EncryptedPacketConn {
     /// Create a new EncryptedPacketConn with the given private key and config.
     pub fn new(secret: SigningKey, config: Config) -> Self {
         let curve_priv = ed25519_private_to_curve25519(&secret);
+        let mut manager = SessionManager::new();
+        manager.notify = config.session_notify.clone();
         let inner = Arc::new(PacketConnImpl::new(secret.clone(), config));
-        let sessions = Arc::new(Mutex::new(SessionManager::new()));
+        let sessions = Arc::new(Mutex::new(manager));
         let (recv_tx, recv_rx) = mpsc::channel(RECV_CHANNEL_SIZE);
         let cancel = CancellationToken::new();
 
@@ -111,6 +134,51 @@ impl EncryptedPacketConn {
         self.inner.get_tree().await
     }
 
+    /// Get all sessions, including handshakes still in progress.
+    pub async fn get_sessions(&self) -> Vec<SessionEntry> {
+        let mgr = self.sessions.lock().await;
+        let mut result: Vec<SessionEntry> = mgr
+            .sessions
+            .values()
+            .map(|info| SessionEntry {
+                key: info.ed,
+                established: true,
+                buffered: false,
+                rx_bytes: info.rx,
+                tx_bytes: info.tx,
+                uptime: info.since.elapsed(),
+                since_rekey: info.rotated.map(|t| t.elapsed()),
+                since_activity: info.last_activity.elapsed(),
+                local_key_seq: info.local_key_seq,
+                remote_key_seq: info.remote_key_seq,
+            })
+            .collect();
+        for (key, buf) in &mgr.buffers {
+            if mgr.sessions.contains_key(key) {
+                continue;
+            }
+            result.push(SessionEntry {
+                key: *key,
+                established: false,
+                buffered: buf.data.is_some(),
+                rx_bytes: 0,
+                tx_bytes: 0,
+                uptime: buf.created.elapsed(),
+                since_rekey: None,
+                since_activity: buf.created.elapsed(),
+                local_key_seq: buf.init.key_seq,
+                remote_key_seq: 0,
+            });
+        }
+        result.sort_by_key(|s| s.key);
+        result
+    }
+
+    /// Get cached paths and pending lookups (delegates to inner).
+    pub async fn get_paths(&self) -> Vec<crate::core::PathEntry> {
+        self.inner.get_paths().await
+    }
+
     /// Get the number of routing entries.
     pub async fn routing_entries(&self) -> usize {
         self.inner.routing_entries().await
@@ -120,6 +188,11 @@ impl EncryptedPacketConn {
     pub async fn tree_coordinates(&self) -> Vec<crate::wire::PeerPort> {
         self.inner.tree_coordinates().await
     }
+
+    /// Get the number of packets dropped from full queues so far.
+    pub async fn queue_drops(&self) -> crate::core::QueueDrops {
+        self.inner.queue_drops().await
+    }
 }
 
 /// Background reader loop: reads from inner PacketConn, decrypts via sessions, delivers.
@@ -249,8 +322,8 @@ impl crate::types::PacketConn for EncryptedPacketConn {
         Ok(buf.len())
     }
 
-    async fn handle_conn(&self,key: Addr, conn: Box<dyn crate::types::AsyncConn>, prio: u8) -> Result<()> {
-        self.inner.handle_conn(key, conn, prio).await
+    async fn handle_conn(&self,key: Addr, conn: Box<dyn crate::types::AsyncConn>, prio: u8, link_id: u64) -> Result<()> {
+        self.inner.handle_conn(key, conn, prio, link_id).await
     }
 
     fn is_closed(&self) -> bool {
@@ -4,6 +4,7 @@
 //! and forward secrecy using XSalsa20-Poly1305 (via RustCrypto's `crypto_box` crate).
 
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crypto_box::SalsaBox;
@@ -486,6 +487,8 @@ pub(crate) struct SessionBuffer {
 pub(crate) struct SessionManager {
     pub sessions: HashMap<PublicKey, SessionInfo>,
     pub buffers: HashMap<PublicKey, SessionBuffer>,
+    /// Told about sessions being established and expiring.
+    pub notify: Option<Arc<dyn Fn(PublicKey, bool) + Send + Sync>>,
 }
 
 impl SessionManager {
@@ -493,6 +496,7 @@ impl SessionManager {
         Self {
             sessions: HashMap::new(),
             buffers: HashMap::new(),
+            notify: None,
         }
     }
 
@@ -513,7 +517,11 @@ impl SessionManager {
             info.fix_shared(0, 0);
         }
 
-        self.sessions.insert(*ed, info);
+        if self.sessions.insert(*ed, info).is_none() {
+            if let Some(ref cb) = self.notify {
+                cb(*ed, true);
+            }
+        }
         self.sessions.get_mut(ed).unwrap()
     }
 
@@ -753,7 +761,14 @@ impl SessionManager {
 
     /// Clean up expired sessions and buffers.
     pub fn cleanup_expired(&mut self) {
-        self.sessions.retain(|_, info| !info.is_expired());
+        let notify = &self.notify;
+        self.sessions.retain(|key, info| {
+            let expired = info.is_expired();
+            if let (true, Some(cb)) = (expired, notify) {
+                cb(*key, false);
+            }
+            !expired
+        });
         self.buffers.retain(|_, buf| buf.created.elapsed() < SESSION_TIMEOUT);
     }
 }
@@ -912,4 +927,32 @@ mod tests {
         }
         panic!("expected msg2 delivery");
     }
+
+    #[test]
+    fn session_notify() {
+        let (priv_a, pub_a, _) = make_keys();
+        let (priv_b, pub_b, curve_priv_b) = make_keys();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let mut mgr_a = SessionManager::new();
+        let mut mgr_b = SessionManager::new();
+        mgr_b.notify = Some({
+            let seen = seen.clone();
+            Arc::new(move |key, up| seen.lock().unwrap().push((key, up)))
+        });
+
+        let init = match &mgr_a.write_to(&pub_b, b"msg", &priv_a)[0] {
+            OutAction::SendToInner { data, .. } => data.clone(),
+            _ => panic!("expected SendToInner"),
+        };
+        mgr_b.handle_data(&pub_a, &init, &curve_priv_b, &priv_b);
+        mgr_b.handle_data(&pub_a, &init, &curve_priv_b, &priv_b);
+        assert_eq!(*seen.lock().unwrap(), [(pub_a, true)]);
+
+        mgr_b.cleanup_expired();
+        assert_eq!(seen.lock().unwrap().len(), 1);
+        mgr_b.sessions.get_mut(&pub_a).unwrap().last_activity -= SESSION_TIMEOUT + Duration::from_secs(1);
+        mgr_b.cleanup_expired();
+        assert_eq!(*seen.lock().unwrap(), [(pub_a, true), (pub_a, false)]);
+    }
 }
@@ -13,8 +13,8 @@ pub mod encrypted;
 pub mod signed;
 
 // Re-export primary public API
-pub use crate::core::{new_packet_conn, PacketConnImpl, PeerInfo, TreeEntry};
-pub use crate::encrypted::{new_encrypted_packet_conn, EncryptedPacketConn};
+pub use crate::core::{new_packet_conn, PacketConnImpl, PathEntry, PeerInfo, QueueDrops, TreeEntry};
+pub use crate::encrypted::{new_encrypted_packet_conn, EncryptedPacketConn, SessionEntry};
 pub use crate::signed::{new_signed_packet_conn, SignedPacketConn};
 pub use crate::types::{Addr, Error, PacketConn, Result};
 pub use crate::config::Config;
@@ -4,9 +4,10 @@
 //! Maintains a cache of known paths to destinations with timeouts.
 //! Throttles lookups to prevent flooding.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant};
 
+use crate::core::PathEntry;
 use crate::crypto::{Crypto, PublicKey, Sig};
 use crate::wire::{self, PeerPort};
 
@@ -270,6 +271,46 @@ impl Pathfinder {
         }
     }
 
+    /// Snapshot of known paths and of lookups still waiting for one.
+    /// A rumor is listed under the key of its cached packet's destination
+    /// if it has one, else under the transformed key it was looked up by.
+    /// Rumors are kept after they are answered, so those that `xform` maps
+    /// a known path's key to are left out.
+    pub fn entries(&self, throttle: Duration, xform: impl Fn(&PublicKey) -> PublicKey) -> Vec<PathEntry> {
+        let answered: HashSet<PublicKey> = self.paths.keys().map(&xform).collect();
+        let mut result: Vec<PathEntry> = self
+            .paths
+            .iter()
+            .map(|(key, info)| PathEntry {
+                key: *key,
+                path: info.path.clone(),
+                seq: info.seq,
+                age: info.last_refresh.elapsed(),
+                // Lookups for a known path are only sent once it breaks
+                lookup_pending: info.broken,
+                throttled: info.req_time.elapsed() < throttle,
+                broken: info.broken,
+            })
+            .collect();
+        for (xformed, rumor) in &self.rumors {
+            if answered.contains(xformed) {
+                continue;
+            }
+            let key = rumor.traffic.as_ref().map_or(*xformed, |t| t.dest);
+            result.push(PathEntry {
+                key,
+                path: Vec::new(),
+                seq: 0,
+                age: rumor.created.elapsed(),
+                lookup_pending: true,
+                throttled: rumor.send_time.elapsed() < throttle,
+                broken: false,
+            });
+        }
+        result.sort_by_key(|e| e.key);
+        result
+    }
+
     /// Clean up expired paths and rumors.
     pub fn cleanup_expired(&mut self, path_timeout: Duration) {
         let now = Instant::now();
@@ -360,6 +401,37 @@ mod tests {
         assert_eq!(pf.paths[&source].path, vec![1, 2]);
     }
 
+    #[test]
+    fn entries() {
+        let crypto = make_crypto();
+        let mut pf = Pathfinder::new(&crypto);
+        let throttle = Duration::from_secs(1);
+        let (known, looking) = ([1u8; 32], [2u8; 32]);
+        let xform = |key: &PublicKey| {
+            let mut partial = *key;
+            partial[16..].fill(0);
+            partial
+        };
+
+        pf.ensure_rumor(xform(&known));
+        pf.ensure_rumor(xform(&looking));
+        pf.accept_notify(known, xform(&known), 7, vec![3, 4], Duration::from_secs(60));
+
+        let entries = pf.entries(throttle, xform);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].key, known);
+        assert_eq!(entries[0].path, vec![3, 4]);
+        assert_eq!(entries[0].seq, 7);
+        assert!(!entries[0].lookup_pending);
+        assert_eq!(entries[1].key, xform(&looking));
+        assert!(entries[1].path.is_empty());
+        assert!(entries[1].lookup_pending && entries[1].throttled);
+
+        pf.handle_broken(&known);
+        assert!(pf.entries(throttle, xform)[0].lookup_pending);
+        assert!(!pf.entries(Duration::ZERO, xform)[1].throttled);
+    }
+
     #[test]
     fn handle_broken() {
         let crypto = make_crypto();
@@ -7,6 +7,7 @@
 //!   writes them with buffered I/O, manages keepalive and deadlines.
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -37,6 +38,7 @@ pub(crate) struct PeerHandle {
     pub port: PeerPort,
     pub prio: u8,
     pub order: u64,
+    pub link_id: u64,
     pub tx: mpsc::Sender<PeerMessage>,
     pub cancel: CancellationToken,
     /// Queue for outbound traffic when writer is busy.
@@ -51,6 +53,7 @@ impl PeerHandle {
             port: self.port,
             prio: self.prio,
             order: self.order,
+            link_id: self.link_id,
         }
     }
 }
@@ -65,6 +68,8 @@ pub(crate) struct Peers {
     pub handles: HashMap<PublicKey, HashMap<PeerId, PeerHandle>>,
     /// Connection order counter.
     order: u64,
+    /// Packets dropped from peer send queues under backpressure.
+    pub send_drops: Arc<AtomicU64>,
 }
 
 impl Peers {
@@ -75,6 +80,7 @@ impl Peers {
             used_ports: HashMap::new(),
             handles: HashMap::new(),
             order: 0,
+            send_drops: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -84,6 +90,7 @@ impl Peers {
         &mut self,
         key: PublicKey,
         prio: u8,
+        link_id: u64,
         tx: mpsc::Sender<PeerMessage>,
         cancel: CancellationToken,
     ) -> PeerHandle {
@@ -116,6 +123,7 @@ impl Peers {
             port,
             prio,
             order,
+            link_id,
             tx,
             cancel,
             traffic_queue: Arc::new(tokio::sync::Mutex::new(PacketQueue::new())),
@@ -130,6 +138,7 @@ impl Peers {
                 port,
                 prio,
                 order,
+                link_id,
                 tx: handle.tx.clone(),
                 cancel: handle.cancel.clone(),
                 traffic_queue: handle.traffic_queue.clone(),
@@ -218,6 +227,7 @@ async fn send_traffic_to_peer(
 
     let tx = handle.tx.clone();
     let traffic_queue = handle.traffic_queue.clone();
+    let send_drops = peers_lock.send_drops.clone();
 
     // Encode the traffic frame
     let wire_traffic = wire::Traffic {
@@ -248,6 +258,7 @@ async fn send_traffic_to_peer(
             if let Some(age) = queue.oldest_age() {
                 if age > MAX_PACKET_AGE_SEND {
                     if queue.drop_largest() {
+                        send_drops.fetch_add(1, Ordering::Relaxed);
                         tracing::warn!(
                             "send_traffic_to_peer[{}]: dropped oldest packet (age={:?} > 25ms) - backpressure applied",
                             peer_id,
@@ -124,6 +124,8 @@ pub(crate) struct PeerEntry {
     pub port: PeerPort,
     pub prio: u8,
     pub order: u64,
+    /// Caller's id for the link, see `PacketConn::handle_conn`.
+    pub link_id: u64,
 }
 
 /// Signature request (seq + nonce).
@@ -256,11 +258,15 @@ pub(crate) struct Router {
     pub path_throttle: Duration,
     pub bloom_transform: Option<std::sync::Arc<dyn Fn(PublicKey) -> PublicKey + Send + Sync>>,
     pub path_notify_cb: Option<std::sync::Arc<dyn Fn(PublicKey) + Send + Sync>>,
+    pub tree_notify_cb: Option<std::sync::Arc<dyn Fn(PublicKey, PublicKey) + Send + Sync>>,
+    /// Root and parent as last reported to `tree_notify_cb`.
+    tree: (PublicKey, PublicKey),
 }
 
 impl Router {
     pub fn new(crypto: Crypto, config: &crate::config::Config) -> Self {
         let pathfinder = Pathfinder::new(&crypto);
+        let tree = (crypto.public_key, crypto.public_key);
         Self {
             crypto,
             pathfinder,
@@ -288,6 +294,8 @@ impl Router {
             path_throttle: config.path_throttle,
             bloom_transform: config.bloom_transform.clone(),
             path_notify_cb: config.path_notify.clone(),
+            tree_notify_cb: config.tree_notify.clone(),
+            tree,
         }
     }
 
@@ -315,9 +323,25 @@ impl Router {
         actions.extend(self.send_announces());
         actions.extend(self.blooms_maintenance());
         self.pathfinder.cleanup_expired(self.path_timeout);
+        self.notify_tree();
         actions
     }
 
+    /// Report our root and parent to `tree_notify_cb` if either changed
+    /// since the last call.
+    fn notify_tree(&mut self) {
+        let self_key = self.crypto.public_key;
+        let parent = self.infos.get(&self_key).map(|i| i.parent).unwrap_or(self_key);
+        let (root, _) = self.get_root_and_dists(&self_key);
+        if (root, parent) == self.tree {
+            return;
+        }
+        self.tree = (root, parent);
+        if let Some(ref cb) = self.tree_notify_cb {
+            cb(root, parent);
+        }
+    }
+
     fn reset_cache(&mut self) {
         self.cache.clear();
     }
@@ -960,12 +984,28 @@ impl Router {
         dist
     }
 
-    fn get_cost(&self, peer_id: PeerId) -> u64 {
+    pub fn get_cost(&self, peer_id: PeerId) -> u64 {
         let lag = self.lags.get(&peer_id).copied().unwrap_or(UNKNOWN_LATENCY);
         let c = lag.as_millis() as u64;
         if c == 0 { 1 } else { c }
     }
 
+    /// Measured latency of a peer link, if there is a measurement yet.
+    pub fn latency(&self, peer_id: PeerId) -> Option<Duration> {
+        self.lags.get(&peer_id).copied().filter(|l| *l != UNKNOWN_LATENCY)
+    }
+
+    /// The link `lookup` picks among several to the same key: lowest
+    /// priority value, then lowest cost, then the oldest.
+    pub fn preferred_peer(&self, key: &PublicKey) -> Option<PeerId> {
+        self.peers.get(key).and_then(|peers| {
+            peers
+                .values()
+                .min_by_key(|e| (e.prio, self.get_cost(e.id), e.order))
+                .map(|e| e.id)
+        })
+    }
+
     /// Greedy routing lookup: find the best next-hop peer.
     pub fn lookup(&mut self, path: &[PeerPort], watermark: &mut u64) -> Option<PeerId> {
         let self_key = self.crypto.public_key;
@@ -1264,8 +1304,8 @@ impl Router {
             notify.info.path.clone(),
             self.path_timeout,
         ) {
-            // Send the cached traffic
-            actions.extend(self.route_traffic(traffic));
+            // Send the cached traffic along the path we just learned
+            actions.extend(self.send_traffic(traffic));
         }
 
         // Notify callback
@@ -1399,6 +1439,33 @@ mod tests {
         assert_eq!(info.parent, self_key); // self-rooted
     }
 
+    #[test]
+    fn tree_notify_on_parent_change() {
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let config = {
+            let seen = seen.clone();
+            crate::config::Config::default().with_tree_notify(move |root, parent| {
+                seen.lock().unwrap().push((root, parent));
+            })
+        };
+        let mut router = Router::new(Crypto::new(SigningKey::generate(&mut OsRng)), &config);
+        let self_key = router.crypto.public_key;
+
+        // Becoming our own root is where we start from, so it is not reported
+        router.do_maintenance();
+        assert!(seen.lock().unwrap().is_empty());
+
+        let other = Crypto::new(SigningKey::generate(&mut OsRng)).public_key;
+        let mut info = router.infos[&self_key].clone();
+        router.infos.insert(other, info.clone());
+        router.infos.get_mut(&other).unwrap().parent = other;
+        info.parent = other;
+        router.infos.insert(self_key, info);
+        router.notify_tree();
+        router.notify_tree();
+        assert_eq!(*seen.lock().unwrap(), [(other, other)]);
+    }
+
     #[test]
     fn update_accepts_newer_seq() {
         let mut router = make_router();
@@ -1484,4 +1551,23 @@ mod tests {
 
         assert!(ann.check());
     }
+
+    #[test]
+    fn preferred_peer_by_priority_cost_and_order() {
+        let mut router = make_router();
+        let key = [7u8; 32];
+        for (id, prio, order) in [(1, 0, 0), (2, 0, 1), (3, 1, 2)] {
+            router.add_peer(PeerEntry { id, key, port: id, prio, order, link_id: id });
+        }
+        // All unmeasured: the oldest link of the best priority
+        assert_eq!(router.preferred_peer(&key), Some(1));
+        assert_eq!(router.latency(1), None);
+
+        router.lags.insert(1, Duration::from_millis(80));
+        router.lags.insert(2, Duration::from_millis(20));
+        router.lags.insert(3, Duration::from_millis(1));
+        assert_eq!(router.preferred_peer(&key), Some(2));
+        assert_eq!(router.latency(2), Some(Duration::from_millis(20)));
+        assert_eq!(router.preferred_peer(&[8u8; 32]), None);
+    }
 }
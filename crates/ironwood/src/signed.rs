@@ -75,6 +75,36 @@ impl SignedPacketConn {
         }
     }
 
+    /// Get info about all connected peers (delegates to inner).
+    pub async fn get_peers(&self) -> Vec<crate::core::PeerInfo> {
+        self.inner.get_peers().await
+    }
+
+    /// Get spanning tree entries (delegates to inner).
+    pub async fn get_tree(&self) -> Vec<crate::core::TreeEntry> {
+        self.inner.get_tree().await
+    }
+
+    /// Get cached paths and pending lookups (delegates to inner).
+    pub async fn get_paths(&self) -> Vec<crate::core::PathEntry> {
+        self.inner.get_paths().await
+    }
+
+    /// Get the number of routing entries.
+    pub async fn routing_entries(&self) -> usize {
+        self.inner.routing_entries().await
+    }
+
+    /// Get our current tree coordinates (path from root).
+    pub async fn tree_coordinates(&self) -> Vec<crate::wire::PeerPort> {
+        self.inner.tree_coordinates().await
+    }
+
+    /// Get the number of packets dropped from full queues so far.
+    pub async fn queue_drops(&self) -> crate::core::QueueDrops {
+        self.inner.queue_drops().await
+    }
+
     /// Sign a message for a specific recipient.
     ///
     /// Signs `[toKey || msg]` and returns `[signature(64) || msg]`.
@@ -199,8 +229,9 @@ impl crate::types::PacketConn for SignedPacketConn {
         key: Addr,
         conn: Box<dyn crate::types::AsyncConn>,
         prio: u8,
+        link_id: u64,
     ) -> Result<()> {
-        self.inner.handle_conn(key, conn, prio).await
+        self.inner.handle_conn(key, conn, prio, link_id).await
     }
 
     fn is_closed(&self) -> bool {
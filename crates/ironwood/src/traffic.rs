@@ -4,7 +4,7 @@
 //! providing fair scheduling across flows. When dropping, it removes
 //! the oldest packet from the largest source within the largest destination.
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -287,6 +287,8 @@ pub(crate) struct DeliveryQueue {
     queue: tokio::sync::Mutex<PacketQueue>,
     /// Number of readers waiting (atomic for lock-free check).
     recv_ready: AtomicUsize,
+    /// Packets dropped because nobody read them in time.
+    drops: AtomicU64,
 }
 
 impl DeliveryQueue {
@@ -294,6 +296,7 @@ impl DeliveryQueue {
         Arc::new(Self {
             queue: tokio::sync::Mutex::new(PacketQueue::new()),
             recv_ready: AtomicUsize::new(0),
+            drops: AtomicU64::new(0),
         })
     }
 
@@ -313,8 +316,8 @@ impl DeliveryQueue {
 
         // Check if the oldest packet is too old (>25ms), if so drop it
         if let Some(age) = queue.oldest_age() {
-            if age > MAX_PACKET_AGE {
-                queue.drop_largest();
+            if age > MAX_PACKET_AGE && queue.drop_largest() {
+                self.drops.fetch_add(1, Ordering::Relaxed);
                 tracing::debug!("Dropped oldest packet from queue (age > 25ms)");
             }
         }
@@ -337,6 +340,11 @@ impl DeliveryQueue {
             None
         }
     }
+
+    /// Number of packets dropped so far.
+    pub fn drops(&self) -> u64 {
+        self.drops.load(Ordering::Relaxed)
+    }
 }
 
 #[cfg(test)]
@@ -390,6 +398,18 @@ mod tests {
         assert_eq!(count, 3);
     }
 
+    #[tokio::test]
+    async fn delivery_queue_counts_drops() {
+        let dq = DeliveryQueue::new();
+        assert!(dq.deliver(make_packet(1, 2, b"a")).await.is_none());
+        assert!(dq.deliver(make_packet(1, 2, b"b")).await.is_none());
+        assert_eq!(dq.drops(), 0);
+
+        tokio::time::sleep(MAX_PACKET_AGE * 2).await;
+        assert!(dq.deliver(make_packet(1, 2, b"c")).await.is_none());
+        assert_eq!(dq.drops(), 1);
+    }
+
     #[test]
     fn same_dest_different_sources() {
         let mut q = PacketQueue::new();
@@ -88,11 +88,14 @@ pub trait PacketConn: Send + Sync {
     async fn write_to(&self, buf: &[u8], addr: &Addr) -> Result<usize>;
 
     /// Accept a peer connection with the given public key and priority.
+    /// `link_id` is the caller's id for the connection, reported back in
+    /// `PeerInfo::link_id` to tell links to the same key apart.
     async fn handle_conn(
         &self,
         key: Addr,
         conn: Box<dyn AsyncConn>,
         prio: u8,
+        link_id: u64,
     ) -> Result<()>;
 
     /// Check if the connection is closed.
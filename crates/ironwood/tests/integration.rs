@@ -29,10 +29,10 @@ async fn connect_nodes(
     let b2 = Arc::clone(b);
 
     let ha = tokio::spawn(async move {
-        let _ = a2.handle_conn(addr_b, Box::new(stream_a), 0).await;
+        let _ = a2.handle_conn(addr_b, Box::new(stream_a), 0, 0).await;
     });
     let hb = tokio::spawn(async move {
-        let _ = b2.handle_conn(addr_a, Box::new(stream_b), 0).await;
+        let _ = b2.handle_conn(addr_a, Box::new(stream_b), 0, 0).await;
     });
 
     (ha, hb)
@@ -238,6 +238,35 @@ async fn three_node_chain() {
     node_c.close().await.unwrap();
 }
 
+/// The packet that triggers a path lookup is held until the path is known
+/// and must then be delivered, not dropped: one-shot protocol requests
+/// (e.g. NodeInfo) have no retransmission to fall back on.
+#[tokio::test]
+async fn first_packet_survives_lookup() {
+    let key_a = SigningKey::generate(&mut OsRng);
+    let key_b = SigningKey::generate(&mut OsRng);
+
+    let node_a = new_packet_conn(key_a, Config::default());
+    let node_b = new_packet_conn(key_b, Config::default());
+
+    let (_ha, _hb) = connect_nodes(&node_a, &node_b).await;
+
+    // Let the tree settle so the lookup is not dropped
+    tokio::time::sleep(Duration::from_secs(8)).await;
+
+    node_a.write_to(b"once", &node_b.local_addr()).await.unwrap();
+
+    let mut buf = vec![0u8; 4096];
+    let (n, from) = read_nonempty(node_b.as_ref(), &mut buf, Duration::from_secs(10))
+        .await
+        .expect("first packet was lost");
+    assert_eq!(&buf[..n], b"once");
+    assert_eq!(from, node_a.local_addr());
+
+    node_a.close().await.unwrap();
+    node_b.close().await.unwrap();
+}
+
 #[tokio::test]
 async fn two_node_encrypted() {
     let key_a = SigningKey::generate(&mut OsRng);
@@ -286,6 +315,11 @@ async fn two_node_encrypted() {
         Err(_) => panic!("timeout"),
     }
 
+    let sessions = node_b.get_sessions().await;
+    let session = sessions.iter().find(|s| s.key == addr_a.0).expect("session with A");
+    assert!(session.established);
+    assert!(session.rx_bytes >= b"encrypted hello".len() as u64);
+
     node_a.close().await.unwrap();
     node_b.close().await.unwrap();
 }
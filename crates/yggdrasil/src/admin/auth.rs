@@ -0,0 +1,242 @@
+//! Admin socket authentication.
+//!
+//! With `admin_credentials` configured, every request has to carry either a
+//! configured `token` or an `auth` object signed by one of the configured
+//! Ed25519 keys. The signature covers the request name, its arguments, a
+//! Unix timestamp and a random nonce (see `signed_message`). Signed requests
+//! more than `SIGNATURE_WINDOW` away from our clock, or seen before, are
+//! refused. Each credential grants read or control access.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::AdminRequest;
+use crate::config::{AdminAccess, AdminCredential};
+
+/// How far a signed request's timestamp may be from our clock.
+const SIGNATURE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Signature of an admin request.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AdminSignature {
+    /// Signer's public key (hex).
+    pub key: String,
+    /// Unix time of signing, in seconds.
+    pub time: u64,
+    /// Random hex string, so that identical requests sign differently.
+    pub nonce: String,
+    /// Ed25519 signature (hex) over `signed_message`.
+    pub signature: String,
+}
+
+impl AdminSignature {
+    /// Sign a request for sending now.
+    pub fn sign(signing_key: &SigningKey, request: &str, arguments: &serde_json::Value) -> Self {
+        let mut nonce = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let nonce = hex::encode(nonce);
+        let time = unix_time();
+        let signature = signing_key.sign(&signed_message(request, arguments, time, &nonce));
+        Self {
+            key: hex::encode(signing_key.verifying_key().to_bytes()),
+            time,
+            nonce,
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+}
+
+/// The bytes an admin request signature covers.
+pub fn signed_message(request: &str, arguments: &serde_json::Value, time: u64, nonce: &str) -> Vec<u8> {
+    format!("yggdrasil-admin\n{}\n{}\n{}\n{}", request, arguments, time, nonce).into_bytes()
+}
+
+/// Access a request needs: control for calls that change the node, read
+/// for everything else.
+pub fn required_access(request: &str) -> AdminAccess {
+    match request.to_lowercase().as_str() {
+        "addpeer" | "removepeer" | "addlistener" | "removelistener" | "reloadconfig" => AdminAccess::Control,
+        _ => AdminAccess::Read,
+    }
+}
+
+/// The configured credentials and the signatures recently accepted.
+pub struct AdminAuth {
+    tokens: Vec<(String, AdminAccess)>,
+    keys: HashMap<[u8; 32], (VerifyingKey, AdminAccess)>,
+    seen: Mutex<HashMap<[u8; 64], u64>>,
+}
+
+impl AdminAuth {
+    pub fn new(credentials: &[AdminCredential]) -> Result<Self, String> {
+        let mut tokens = Vec::new();
+        let mut keys = HashMap::new();
+        for credential in credentials {
+            match (credential.token.is_empty(), credential.key.is_empty()) {
+                (false, true) => tokens.push((credential.token.clone(), credential.access)),
+                (true, false) => {
+                    let key = parse_key(&credential.key)?;
+                    keys.insert(key.to_bytes(), (key, credential.access));
+                }
+                _ => return Err("each admin credential needs either a token or a key".to_string()),
+            }
+        }
+        Ok(Self {
+            tokens,
+            keys,
+            seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Whether requests have to authenticate at all.
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || !self.keys.is_empty()
+    }
+
+    /// Check the request's credentials and return the access they grant.
+    pub fn authorize(&self, req: &AdminRequest) -> Result<AdminAccess, String> {
+        if !self.is_enabled() {
+            return Ok(AdminAccess::Control);
+        }
+        if let Some(token) = &req.token {
+            return self
+                .tokens
+                .iter()
+                .find(|(t, _)| constant_time_eq(t.as_bytes(), token.as_bytes()))
+                .map(|(_, access)| *access)
+                .ok_or_else(|| "invalid admin token".to_string());
+        }
+        if let Some(auth) = &req.auth {
+            return self.verify(req, auth);
+        }
+        Err("authentication required".to_string())
+    }
+
+    fn verify(&self, req: &AdminRequest, auth: &AdminSignature) -> Result<AdminAccess, String> {
+        let key = parse_key(&auth.key)?;
+        let (key, access) = self
+            .keys
+            .get(&key.to_bytes())
+            .ok_or_else(|| "unknown admin key".to_string())?;
+        let signature: [u8; 64] = hex::decode(&auth.signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or("invalid signature encoding")?;
+
+        let now = unix_time();
+        if now.abs_diff(auth.time) > SIGNATURE_WINDOW.as_secs() {
+            return Err("signed request expired, check the clocks".to_string());
+        }
+        key.verify(
+            &signed_message(&req.request, &req.arguments, auth.time, &auth.nonce),
+            &Signature::from_bytes(&signature),
+        )
+        .map_err(|_| "invalid signature".to_string())?;
+
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, time| now.abs_diff(*time) <= SIGNATURE_WINDOW.as_secs());
+        if seen.insert(signature, auth.time).is_some() {
+            return Err("replayed request".to_string());
+        }
+        Ok(*access)
+    }
+}
+
+fn parse_key(key: &str) -> Result<VerifyingKey, String> {
+    hex::decode(key)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| format!("invalid admin key: {}", key))
+}
+
+/// Compare without leaking where the first difference is.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    fn request(name: &str) -> AdminRequest {
+        AdminRequest {
+            request: name.to_string(),
+            arguments: serde_json::json!({ "uri": "tcp://192.0.2.1:9001" }),
+            ..Default::default()
+        }
+    }
+
+    fn credential(token: &str, key: &str, access: AdminAccess) -> AdminCredential {
+        AdminCredential { token: token.to_string(), key: key.to_string(), access }
+    }
+
+    #[test]
+    fn test_tokens() {
+        let open = AdminAuth::new(&[]).unwrap();
+        assert_eq!(open.authorize(&request("addPeer")), Ok(AdminAccess::Control));
+
+        let auth = AdminAuth::new(&[
+            credential("reader", "", AdminAccess::Read),
+            credential("admin", "", AdminAccess::Control),
+        ])
+        .unwrap();
+        let mut req = request("getPeers");
+        assert!(auth.authorize(&req).is_err());
+        req.token = Some("reader".to_string());
+        assert_eq!(auth.authorize(&req), Ok(AdminAccess::Read));
+        req.token = Some("admin".to_string());
+        assert_eq!(auth.authorize(&req), Ok(AdminAccess::Control));
+        req.token = Some("admin2".to_string());
+        assert!(auth.authorize(&req).is_err());
+
+        assert_eq!(required_access("getPeers"), AdminAccess::Read);
+        assert_eq!(required_access("removePeer"), AdminAccess::Control);
+        assert_eq!(required_access("reloadConfig"), AdminAccess::Control);
+        assert_eq!(required_access("addListener"), AdminAccess::Control);
+        assert!(AdminAuth::new(&[credential("", "", AdminAccess::Read)]).is_err());
+        assert!(AdminAuth::new(&[credential("", "00", AdminAccess::Read)]).is_err());
+    }
+
+    #[test]
+    fn test_signatures() {
+        let key = SigningKey::generate(&mut OsRng);
+        let public = hex::encode(key.verifying_key().to_bytes());
+        let auth = AdminAuth::new(&[credential("", &public, AdminAccess::Control)]).unwrap();
+
+        let mut req = request("addPeer");
+        req.auth = Some(AdminSignature::sign(&key, &req.request, &req.arguments));
+        assert_eq!(auth.authorize(&req), Ok(AdminAccess::Control));
+        assert_eq!(auth.authorize(&req), Err("replayed request".to_string()));
+
+        // The signature covers the arguments
+        let mut tampered = request("addPeer");
+        tampered.auth = Some(AdminSignature::sign(&key, &tampered.request, &tampered.arguments));
+        tampered.arguments = serde_json::json!({ "uri": "tcp://198.51.100.1:9001" });
+        assert_eq!(auth.authorize(&tampered), Err("invalid signature".to_string()));
+
+        let mut old = request("addPeer");
+        let mut signature = AdminSignature::sign(&key, &old.request, &old.arguments);
+        signature.time -= 2 * SIGNATURE_WINDOW.as_secs();
+        old.auth = Some(signature);
+        assert!(auth.authorize(&old).is_err());
+
+        let mut stranger = request("addPeer");
+        let other = SigningKey::generate(&mut OsRng);
+        stranger.auth = Some(AdminSignature::sign(&other, &stranger.request, &stranger.arguments));
+        assert_eq!(auth.authorize(&stranger), Err("unknown admin key".to_string()));
+    }
+}
@@ -0,0 +1,761 @@
+mod auth;
+
+use std::future::Future;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio_util::sync::CancellationToken;
+
+pub use self::auth::{signed_message, AdminSignature};
+
+use crate::address::{addr_for_key, subnet_for_key};
+use crate::config::{AdminAccess, AdminCredential};
+use crate::core::Core;
+use crate::crawl::{self, CrawlOptions};
+use crate::events::EVENT_NAMES;
+use crate::links;
+use crate::persist::ListEdit;
+#[cfg(unix)]
+use crate::links::unix;
+
+use self::auth::AdminAuth;
+
+/// JSON-RPC request format.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AdminRequest {
+    pub request: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+    #[serde(default)]
+    pub keepalive: bool,
+    /// Shared secret from `admin_credentials`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Signature by a key from `admin_credentials`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<AdminSignature>,
+}
+
+/// JSON-RPC response format.
+#[derive(Debug, Serialize)]
+pub struct AdminResponse {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub request: AdminRequest,
+    pub response: serde_json::Value,
+}
+
+impl AdminResponse {
+    /// Response to `request`, which is echoed back without its credentials.
+    fn new(mut request: AdminRequest, result: Result<serde_json::Value, String>) -> Self {
+        request.token = None;
+        request.auth = None;
+        match result {
+            Ok(response) => Self {
+                status: "success".to_string(),
+                error: None,
+                request,
+                response,
+            },
+            Err(e) => Self {
+                status: "error".to_string(),
+                error: Some(e),
+                request,
+                response: serde_json::Value::Null,
+            },
+        }
+    }
+}
+
+/// Admin socket for monitoring and controlling the node.
+pub struct AdminSocket {
+    cancel: CancellationToken,
+    handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl AdminSocket {
+    /// Start the admin socket on the given address.
+    /// Address format: "tcp://host:port" or "unix:///path/to/socket", the
+    /// latter optionally with `?mode=660&group=name` for the socket file.
+    pub async fn new(listen_addr: &str, core: Arc<Core>) -> Result<Self, String> {
+        if listen_addr.is_empty() || listen_addr == "none" {
+            return Ok(Self {
+                cancel: CancellationToken::new(),
+                handle: None,
+            });
+        }
+
+        let auth = Arc::new(AdminAuth::new(&core.config().admin_credentials)?);
+
+        let cancel = CancellationToken::new();
+        let handle = if let Some(addr) = listen_addr.strip_prefix("tcp://") {
+            let listener = TcpListener::bind(addr)
+                .await
+                .map_err(|e| format!("admin socket bind failed: {}", e))?;
+            let actual_addr = listener
+                .local_addr()
+                .map_err(|e| format!("admin local_addr: {}", e))?;
+            tracing::info!("Admin socket listening on tcp://{}", actual_addr);
+            tokio::spawn(accept_loop(listener, core, auth, cancel.clone()))
+        } else if listen_addr.starts_with("unix://") {
+            listen_unix(listen_addr, core, auth, cancel.clone()).await?
+        } else {
+            return Err(format!("admin listen must start with tcp:// or unix://, got: {}", listen_addr));
+        };
+
+        Ok(Self {
+            cancel,
+            handle: Some(handle),
+        })
+    }
+
+    /// Check `admin_credentials` (`--check-config`).
+    pub fn check_credentials(credentials: &[AdminCredential]) -> Result<(), String> {
+        AdminAuth::new(credentials).map(|_| ())
+    }
+
+    /// Check an `admin_listen` address without binding (`--check-config`).
+    pub fn check_listen(listen_addr: &str) -> Result<(), String> {
+        if listen_addr.is_empty() || listen_addr == "none" {
+            return Ok(());
+        }
+        if listen_addr.starts_with("tcp://") {
+            let url = url::Url::parse(listen_addr).map_err(|e| format!("invalid admin listen {}: {}", listen_addr, e))?;
+            url.host_str().filter(|h| !h.is_empty()).ok_or("missing host")?;
+            url.port().ok_or("missing port")?;
+            Ok(())
+        } else if listen_addr.starts_with("unix://") {
+            #[cfg(unix)]
+            return unix_socket_options(listen_addr).map(|_| ());
+            #[cfg(not(unix))]
+            return Err("unix admin sockets are not supported on this platform".to_string());
+        } else {
+            Err(format!("admin listen must start with tcp:// or unix://, got: {}", listen_addr))
+        }
+    }
+
+    /// Stop the admin socket.
+    pub fn close(&self) {
+        self.cancel.cancel();
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+    }
+}
+
+/// Listeners the admin socket can accept connections from.
+trait Listener: Send + 'static {
+    type Stream: AsyncRead + AsyncWrite + Send + 'static;
+
+    fn accept(&self) -> impl Future<Output = std::io::Result<Self::Stream>> + Send;
+}
+
+impl Listener for TcpListener {
+    type Stream = tokio::net::TcpStream;
+
+    async fn accept(&self) -> std::io::Result<Self::Stream> {
+        TcpListener::accept(self).await.map(|(stream, _)| stream)
+    }
+}
+
+#[cfg(unix)]
+impl Listener for tokio::net::UnixListener {
+    type Stream = tokio::net::UnixStream;
+
+    async fn accept(&self) -> std::io::Result<Self::Stream> {
+        tokio::net::UnixListener::accept(self).await.map(|(stream, _)| stream)
+    }
+}
+
+async fn accept_loop<L: Listener>(listener: L, core: Arc<Core>, auth: Arc<AdminAuth>, cancel: CancellationToken) {
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            result = listener.accept() => {
+                match result {
+                    Ok(stream) => {
+                        let core = core.clone();
+                        let auth = auth.clone();
+                        tokio::spawn(async move {
+                            handle_admin_conn(stream, core, auth).await;
+                        });
+                    }
+                    Err(e) => {
+                        tracing::error!("Admin accept error: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Listen on a unix domain socket. Access is controlled by the socket
+/// file's mode (default 0660) and group.
+#[cfg(unix)]
+async fn listen_unix(
+    listen_addr: &str,
+    core: Arc<Core>,
+    auth: Arc<AdminAuth>,
+    cancel: CancellationToken,
+) -> Result<tokio::task::JoinHandle<()>, String> {
+    let (path, mode, group) = unix_socket_options(listen_addr)?;
+    let (listener, guard) = unix::bind(&path, mode).await.map_err(|e| format!("admin socket {}: {}", path.display(), e))?;
+    if let Some(group) = &group {
+        unix::set_group(&path, group)?;
+    }
+    tracing::info!("Admin socket listening on unix://{}", path.display());
+
+    Ok(tokio::spawn(async move {
+        // Socket file is removed when this task ends or is aborted
+        let _guard = guard;
+        accept_loop(listener, core, auth, cancel).await;
+    }))
+}
+
+/// The socket path, mode and group of a `unix://` admin address.
+#[cfg(unix)]
+fn unix_socket_options(listen_addr: &str) -> Result<(std::path::PathBuf, u32, Option<String>), String> {
+    let url = url::Url::parse(listen_addr).map_err(|e| format!("invalid admin listen {}: {}", listen_addr, e))?;
+    let path = unix::socket_path(&url)?;
+    let mut mode = unix::DEFAULT_SOCKET_MODE;
+    let mut group = None;
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "mode" => {
+                mode = u32::from_str_radix(&value, 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o777)
+                    .ok_or_else(|| format!("invalid admin socket mode: {}", value))?;
+            }
+            "group" => group = Some(value.into_owned()),
+            other => return Err(format!("unknown admin socket option: {}", other)),
+        }
+    }
+    Ok((path, mode, group))
+}
+
+#[cfg(not(unix))]
+async fn listen_unix(
+    _listen_addr: &str,
+    _core: Arc<Core>,
+    _auth: Arc<AdminAuth>,
+    _cancel: CancellationToken,
+) -> Result<tokio::task::JoinHandle<()>, String> {
+    Err("unix admin sockets are not supported on this platform".to_string())
+}
+
+async fn handle_admin_conn<S: AsyncRead + AsyncWrite + Send + 'static>(stream: S, core: Arc<Core>, auth: Arc<AdminAuth>) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let mut line = String::new();
+        match reader.read_line(&mut line).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(_) => break,
+        }
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let req: AdminRequest = match serde_json::from_str(line) {
+            Ok(r) => r,
+            Err(e) => {
+                let resp = AdminResponse::new(AdminRequest::default(), Err(format!("failed to parse request: {}", e)));
+                let _ = write_response(&mut writer, &resp).await;
+                break;
+            }
+        };
+
+        let access = auth.authorize(&req);
+        if let Err(e) = &access {
+            tracing::debug!("Admin request {} refused: {}", req.request, e);
+        }
+
+        if req.request.eq_ignore_ascii_case("subscribe") {
+            match access {
+                // Every credential grants read access, which is all events need
+                Ok(access) => subscribe(req, reader, writer, &core, access).await,
+                Err(e) => {
+                    let _ = write_response(&mut writer, &AdminResponse::new(req, Err(e))).await;
+                }
+            }
+            break;
+        }
+
+        let keepalive = req.keepalive;
+        let result = match access {
+            Ok(access) => handle_request(&req, &core, access).await,
+            Err(e) => Err(e),
+        };
+        let resp = AdminResponse::new(req, result);
+
+        let _ = write_response(&mut writer, &resp).await;
+
+        if !keepalive {
+            break;
+        }
+    }
+}
+
+/// Answer a `subscribe` request, then stream events as JSON lines until the
+/// client disconnects. Anything else the client sends is ignored. Link URIs
+/// are redacted unless the client has control access.
+async fn subscribe<R, W>(req: AdminRequest, mut reader: BufReader<R>, mut writer: W, core: &Core, access: AdminAccess)
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let filter = match event_filter(&req) {
+        Ok(filter) => filter,
+        Err(e) => {
+            let _ = write_response(&mut writer, &AdminResponse::new(req, Err(e))).await;
+            return;
+        }
+    };
+    // Subscribe before answering so nothing after the answer is missed
+    let mut events = core.events().subscribe();
+    let resp = AdminResponse::new(req, Ok(serde_json::json!({ "events": filter })));
+    if write_response(&mut writer, &resp).await.is_err() {
+        return;
+    }
+
+    let mut input = String::new();
+    loop {
+        let line = tokio::select! {
+            read = reader.read_line(&mut input) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    input.clear();
+                    continue;
+                }
+            },
+            event = events.recv() => match event {
+                Ok(mut record) if filter.contains(&record.event.name()) => {
+                    if access < AdminAccess::Control {
+                        record.event.redact();
+                    }
+                    serde_json::to_string(&record).unwrap_or_default()
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => serde_json::json!({ "event": "lagged", "missed": missed }).to_string(),
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if write_line(&mut writer, &line).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Events selected by the `events` argument: a list or comma-separated
+/// string of event names, all events if not given.
+fn event_filter(req: &AdminRequest) -> Result<Vec<&'static str>, String> {
+    let names: Vec<String> = match req.arguments.get("events") {
+        None => return Ok(EVENT_NAMES.to_vec()),
+        Some(serde_json::Value::String(s)) => s.split(',').map(|n| n.trim().to_lowercase()).collect(),
+        Some(serde_json::Value::Array(list)) => list
+            .iter()
+            .map(|v| v.as_str().map(str::to_lowercase).ok_or_else(|| format!("invalid event name: {}", v)))
+            .collect::<Result<_, _>>()?,
+        Some(other) => return Err(format!("invalid 'events' argument: {}", other)),
+    };
+    names
+        .iter()
+        .filter(|n| !n.is_empty())
+        .map(|n| {
+            EVENT_NAMES
+                .iter()
+                .find(|known| *known == n)
+                .copied()
+                .ok_or_else(|| format!("unknown event '{}', expected one of {}", n, EVENT_NAMES.join(", ")))
+        })
+        .collect()
+}
+
+async fn handle_request(req: &AdminRequest, core: &Arc<Core>, access: AdminAccess) -> Result<serde_json::Value, String> {
+    if access < auth::required_access(&req.request) {
+        return Err(format!("'{}' needs control access", req.request));
+    }
+    match req.request.to_lowercase().as_str() {
+        "list" => Ok(serde_json::json!({
+            "list": [
+                "list", "getself", "getpeers", "gettree", "getpaths", "getsessions", "getnodeinfo",
+                "debug_remotegetself", "debug_remotegetpeers", "debug_remotegettree",
+                "crawl", "subscribe", "addpeer", "removepeer", "addlistener", "removelistener",
+                "reloadconfig",
+            ],
+        })),
+
+        "getself" => {
+            let routing_entries = core.routing_entries().await;
+            let coordinates = core.tree_coordinates().await;
+            Ok(serde_json::json!({
+                "build_name": env!("CARGO_PKG_NAME"),
+                "build_version": env!("CARGO_PKG_VERSION"),
+                "key": hex::encode(core.public_key()),
+                "address": core.address().to_string(),
+                "subnet": core.subnet().to_string(),
+                "coordinates": coordinates,
+                "routing_entries": routing_entries,
+            }))
+        }
+
+        "getpeers" => {
+            let peers = core.get_peers().await;
+            // Read-only clients don't get passwords or commands, nor dial
+            // errors, which can quote them
+            let redact = access < AdminAccess::Control;
+            let peers_json: Vec<serde_json::Value> = peers
+                .iter()
+                .map(|p| {
+                    serde_json::json!({
+                        "uri": if redact { links::redact_uri(&p.uri) } else { p.uri.clone() },
+                        "remote": p.remote,
+                        "up": p.up,
+                        "state": p.state.as_str(),
+                        "inbound": p.inbound,
+                        "key": p.key.map(hex::encode),
+                        "address": p.key.map(|k| addr_for_key(&k).to_string()),
+                        "subnet": p.key.map(|k| subnet_for_key(&k).to_string()),
+                        "priority": p.priority,
+                        "bytes_recvd": p.rx_bytes,
+                        "bytes_sent": p.tx_bytes,
+                        "rx_rate": p.rx_rate,
+                        "tx_rate": p.tx_rate,
+                        "uptime": p.uptime_secs,
+                        "last_error": if redact { None } else { p.last_error.clone() },
+                        "attempts": p.attempts,
+                        "next_retry": p.next_retry_secs,
+                        "port": p.port,
+                        "latency_ms": p.latency_ms,
+                        "cost": p.cost,
+                        "preferred": p.preferred,
+                    })
+                })
+                .collect();
+            Ok(serde_json::json!({ "peers": peers_json }))
+        }
+
+        "gettree" => {
+            let tree = core.get_tree().await;
+            let tree_json: Vec<serde_json::Value> = tree
+                .iter()
+                .map(|t| {
+                    let address = addr_for_key(&t.key);
+                    serde_json::json!({
+                        "key": hex::encode(t.key),
+                        "address": address.to_string(),
+                        "parent": hex::encode(t.parent),
+                        "sequence": t.sequence,
+                    })
+                })
+                .collect();
+            Ok(serde_json::json!({ "tree": tree_json }))
+        }
+
+        "getpaths" => {
+            let paths = core.get_paths().await;
+            let paths_json: Vec<serde_json::Value> = paths
+                .iter()
+                .map(|p| {
+                    let address = addr_for_key(&p.key);
+                    serde_json::json!({
+                        "key": hex::encode(p.key),
+                        "address": address.to_string(),
+                        "path": p.path,
+                        "sequence": p.seq,
+                        "age": p.age.as_secs_f64(),
+                        "lookup_pending": p.lookup_pending,
+                        "throttled": p.throttled,
+                        "broken": p.broken,
+                    })
+                })
+                .collect();
+            Ok(serde_json::json!({ "paths": paths_json }))
+        }
+
+        "getsessions" => {
+            let sessions = core.get_sessions().await;
+            let sessions_json: Vec<serde_json::Value> = sessions
+                .iter()
+                .map(|s| {
+                    let address = addr_for_key(&s.key);
+                    serde_json::json!({
+                        "key": hex::encode(s.key),
+                        "address": address.to_string(),
+                        "established": s.established,
+                        "buffered": s.buffered,
+                        "bytes_recvd": s.rx_bytes,
+                        "bytes_sent": s.tx_bytes,
+                        "uptime": s.uptime.as_secs_f64(),
+                        "last_rekey": s.since_rekey.map(|d| d.as_secs_f64()),
+                        "last_activity": s.since_activity.as_secs_f64(),
+                        "local_key_seq": s.local_key_seq,
+                        "remote_key_seq": s.remote_key_seq,
+                    })
+                })
+                .collect();
+            Ok(serde_json::json!({ "sessions": sessions_json }))
+        }
+
+        "getnodeinfo" => {
+            let key = key_argument(req)?;
+            let info = core.get_node_info(key).await?;
+            Ok(by_address(&key, info))
+        }
+
+        "debug_remotegetself" => {
+            let key = key_argument(req)?;
+            let info = core.debug_remote_get_self(key).await?;
+            Ok(by_address(&key, info))
+        }
+
+        "debug_remotegetpeers" => {
+            let key = key_argument(req)?;
+            let keys = core.debug_remote_get_peers(key).await?;
+            Ok(by_address(&key, serde_json::json!({ "keys": keys.iter().map(hex::encode).collect::<Vec<_>>() })))
+        }
+
+        "debug_remotegettree" => {
+            let key = key_argument(req)?;
+            let keys = core.debug_remote_get_tree(key).await?;
+            Ok(by_address(&key, serde_json::json!({ "keys": keys.iter().map(hex::encode).collect::<Vec<_>>() })))
+        }
+
+        "crawl" => {
+            let mut options = CrawlOptions::default();
+            if let Some(n) = number_argument(req, "concurrency")? {
+                options.concurrency = n as usize;
+            }
+            if let Some(secs) = number_argument(req, "timeout")? {
+                options.timeout = std::time::Duration::from_secs(secs);
+            }
+            if let Some(n) = number_argument(req, "max_nodes")? {
+                options.max_nodes = n as usize;
+            }
+            let graph = crawl::crawl(core, &options).await;
+            serde_json::to_value(graph).map_err(|e| e.to_string())
+        }
+
+        "addpeer" => {
+            let uri = req
+                .arguments
+                .get("uri")
+                .and_then(|v| v.as_str())
+                .ok_or("missing 'uri' argument")?;
+            if links::is_local_peer(uri) && !core.config().admin_allow_local_peers {
+                return Err("addPeer failed: exec, pipe and serial peers can only be added in the config file, \
+                     unless admin_allow_local_peers is set"
+                    .to_string());
+            }
+            let persist = persist_argument(req, core)?;
+            core.add_peer(uri)
+                .await
+                .map_err(|e| format!("addPeer failed: {}", e))?;
+            if persist {
+                core.persist_list("peers", ListEdit::Add(uri))
+                    .map_err(|e| format!("peer added but not saved: {}", e))?;
+            }
+            Ok(serde_json::json!({}))
+        }
+
+        "removepeer" => {
+            let uri = req
+                .arguments
+                .get("uri")
+                .and_then(|v| v.as_str())
+                .ok_or("missing 'uri' argument")?;
+            let persist = persist_argument(req, core)?;
+            let removed = core.remove_peer(uri).await;
+            if persist {
+                // One that is only in the file can still be removed from it
+                let saved = core.persist_list("peers", ListEdit::Remove(uri)).map_err(|e| match removed {
+                    Ok(()) => format!("peer removed but not saved: {}", e),
+                    Err(_) => format!("removePeer failed: {}", e),
+                })?;
+                if saved {
+                    return Ok(serde_json::json!({}));
+                }
+            }
+            removed.map_err(|e| format!("removePeer failed: {}", e))?;
+            Ok(serde_json::json!({}))
+        }
+
+        "addlistener" => {
+            let uri = req
+                .arguments
+                .get("uri")
+                .and_then(|v| v.as_str())
+                .ok_or("missing 'uri' argument")?;
+            let persist = persist_argument(req, core)?;
+            core.listen(uri)
+                .await
+                .map_err(|e| format!("addListener failed: {}", e))?;
+            if persist {
+                core.persist_list("listen", ListEdit::Add(uri))
+                    .map_err(|e| format!("listener added but not saved: {}", e))?;
+            }
+            Ok(serde_json::json!({}))
+        }
+
+        "removelistener" => {
+            let uri = req
+                .arguments
+                .get("uri")
+                .and_then(|v| v.as_str())
+                .ok_or("missing 'uri' argument")?;
+            let persist = persist_argument(req, core)?;
+            let removed = core.stop_listener(uri).await;
+            if persist {
+                // One that is only in the file can still be removed from it
+                let saved = core.persist_list("listen", ListEdit::Remove(uri)).map_err(|e| match removed {
+                    Ok(()) => format!("listener removed but not saved: {}", e),
+                    Err(_) => format!("removeListener failed: {}", e),
+                })?;
+                if saved {
+                    return Ok(serde_json::json!({}));
+                }
+            }
+            removed.map_err(|e| format!("removeListener failed: {}", e))?;
+            Ok(serde_json::json!({}))
+        }
+
+        "reloadconfig" => {
+            let report = core
+                .reload_config()
+                .await
+                .map_err(|e| format!("reloadConfig failed: {}", e))?;
+            serde_json::to_value(report).map_err(|e| e.to_string())
+        }
+
+        other => Err(format!(
+            "unknown action '{}', try 'list' for help",
+            other
+        )),
+    }
+}
+
+/// The remote node's public key from the `key` argument.
+fn key_argument(req: &AdminRequest) -> Result<[u8; 32], String> {
+    let key = req
+        .arguments
+        .get("key")
+        .and_then(|v| v.as_str())
+        .ok_or("missing 'key' argument")?;
+    hex::decode(key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("invalid public key: {}", key))
+}
+
+/// An optional numeric argument, given as a number or a string (as
+/// yggdrasilctl sends all arguments).
+fn number_argument(req: &AdminRequest, name: &str) -> Result<Option<u64>, String> {
+    match req.arguments.get(name) {
+        None => Ok(None),
+        Some(serde_json::Value::Number(n)) => n.as_u64().map(Some).ok_or_else(|| format!("invalid '{}' argument: {}", name, n)),
+        Some(serde_json::Value::String(s)) => s.parse().map(Some).map_err(|_| format!("invalid '{}' argument: {}", name, s)),
+        Some(other) => Err(format!("invalid '{}' argument: {}", name, other)),
+    }
+}
+
+/// Whether to write the change to the config file: the `persist` argument
+/// (`true`/`false`, as a boolean or a string) or `persist_admin_changes`.
+fn persist_argument(req: &AdminRequest, core: &Core) -> Result<bool, String> {
+    match req.arguments.get("persist") {
+        None => Ok(core.config().persist_admin_changes),
+        Some(serde_json::Value::Bool(b)) => Ok(*b),
+        Some(serde_json::Value::String(s)) => s.parse().map_err(|_| format!("invalid 'persist' argument: {}", s)),
+        Some(other) => Err(format!("invalid 'persist' argument: {}", other)),
+    }
+}
+
+/// A remote node's answer keyed by its address, as yggdrasil-go returns it.
+fn by_address(key: &[u8; 32], info: serde_json::Value) -> serde_json::Value {
+    let mut response = serde_json::Map::new();
+    response.insert(addr_for_key(key).to_string(), info);
+    serde_json::Value::Object(response)
+}
+
+async fn write_response(writer: &mut (impl AsyncWrite + Unpin), resp: &AdminResponse) -> Result<(), std::io::Error> {
+    let json = serde_json::to_string(resp).unwrap_or_default();
+    write_line(writer, &json).await
+}
+
+async fn write_line(writer: &mut (impl AsyncWrite + Unpin), json: &str) -> Result<(), std::io::Error> {
+    writer.write_all(json.as_bytes()).await?;
+    writer.write_all(b"\n").await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+
+    fn add_peer_request(uri: &str) -> AdminRequest {
+        AdminRequest {
+            request: "addpeer".to_string(),
+            arguments: serde_json::json!({ "uri": uri }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_local_peer_refused() {
+        let core = Core::new(SigningKey::generate(&mut OsRng), Config::default());
+        for uri in [
+            "exec:///bin/sh?arg=-c&arg=id",
+            "pipe:cat",
+            "serial:///dev/ttyS0",
+            // The URI parser drops these, so the check must too
+            " exec:/bin/sh?arg=-c&arg=id",
+            "e\txec:/bin/sh",
+            "\nexec:cat",
+            "EXEC:cat",
+        ] {
+            let err = handle_request(&add_peer_request(uri), &core, AdminAccess::Control)
+                .await
+                .unwrap_err();
+            assert!(err.contains("admin_allow_local_peers"), "{}: {}", uri, err);
+        }
+        assert!(core.get_peers().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_peers_redacted_for_read() {
+        let core = Core::new(SigningKey::generate(&mut OsRng), Config::default());
+        core.init_links().await;
+        // Nothing listens on port 1, so the dial fails right away
+        let uri = "tcp://127.0.0.1:1?password=secret";
+        core.add_peer(uri).await.unwrap();
+        let request = AdminRequest { request: "getpeers".to_string(), ..Default::default() };
+
+        let mut control = serde_json::Value::Null;
+        for _ in 0..50 {
+            control = handle_request(&request, &core, AdminAccess::Control).await.unwrap();
+            if !control["peers"][0]["last_error"].is_null() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(control["peers"][0]["uri"], uri);
+        assert!(control["peers"][0]["last_error"].is_string());
+
+        let read = handle_request(&request, &core, AdminAccess::Read).await.unwrap();
+        assert_eq!(read["peers"][0]["uri"], "tcp://127.0.0.1:1");
+        assert!(read["peers"][0]["last_error"].is_null());
+        core.close().await.unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+//! `yggdrasil --check-config`: validating a config file without starting
+//! anything.
+//!
+//! Settings that would otherwise only fail at startup, or be skipped with a
+//! log line, are checked with the same parsers the node uses: the private
+//! key, peer and listen URIs with their options, the admin socket, allowed
+//! public keys, multicast regexes, the MTU and NodeInfo. Every problem names
+//! the setting and, where it can be found, its line in the file.
+
+use std::fmt;
+
+use toml_edit::{ImDocument, Item};
+
+use crate::admin::AdminSocket;
+use crate::config::{parse_public_key, Config, ConfigFormat};
+use crate::links;
+use crate::proto;
+
+/// Smallest MTU IPv6 allows.
+const MIN_MTU: u64 = 1280;
+/// Largest MTU a TUN adapter takes.
+const MAX_MTU: u64 = 65535;
+
+/// Something wrong with one setting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    /// The setting, e.g. `peers[2]`. Empty if the file does not parse far
+    /// enough to tell.
+    pub field: String,
+    /// Line in the file, counting from 1.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// What `check` found.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub errors: Vec<Problem>,
+    /// Settings that are ignored or may not do what was meant.
+    pub warnings: Vec<Problem>,
+}
+
+/// Check a config, TOML or yggdrasil-go, given as the file's text.
+pub fn check(text: &str) -> Report {
+    let mut checker = Checker {
+        lines: Lines::new(text),
+        report: Report::default(),
+    };
+    let config = match ConfigFormat::detect(text) {
+        ConfigFormat::Toml => match toml::from_str::<Config>(text) {
+            Ok(config) => config,
+            Err(e) => {
+                let offset = e.span().map(|span| span.start);
+                checker.report.errors.push(Problem {
+                    field: offset.and_then(|o| checker.lines.field_at(o)).unwrap_or_default(),
+                    line: offset.map(|o| checker.lines.line_of(o)),
+                    message: e.message().trim().replace('\n', ": "),
+                });
+                return checker.report;
+            }
+        },
+        ConfigFormat::Go => match crate::go_config::import(text) {
+            Ok((config, warnings)) => {
+                for warning in warnings {
+                    let problem = checker.lines.go_problem(warning);
+                    checker.report.warnings.push(problem);
+                }
+                config
+            }
+            Err(e) => {
+                let problem = checker.lines.go_problem(e);
+                checker.report.errors.push(problem);
+                return checker.report;
+            }
+        },
+    };
+    checker.check(&config);
+    checker.report
+}
+
+struct Checker<'a> {
+    lines: Lines<'a>,
+    report: Report,
+}
+
+impl Checker<'_> {
+    fn check(&mut self, config: &Config) {
+        if config.private_key.is_empty() {
+            self.warning(
+                "private_key",
+                "not set, the key comes from YGGDRASIL_PRIVATE_KEY or a new one is made at every start",
+            );
+        } else if let Err(e) = config.signing_key() {
+            self.error("private_key", None, None, e);
+        }
+
+        for (i, uri) in config.peers.iter().enumerate() {
+            if let Err(e) = links::check_peer_uri(uri) {
+                self.error("peers", Some(i), Some(uri), e);
+            }
+        }
+        for (i, addr) in config.listen.iter().enumerate() {
+            if let Err(e) = links::check_listen_uri(addr) {
+                self.error("listen", Some(i), Some(addr), e);
+            }
+        }
+
+        if let Err(e) = AdminSocket::check_listen(&config.admin_listen) {
+            self.error("admin_listen", None, None, e);
+        }
+        if let Err(e) = AdminSocket::check_credentials(&config.admin_credentials) {
+            self.error("admin_credentials", None, None, e);
+        }
+
+        for (i, key) in config.allowed_public_keys.iter().enumerate() {
+            if let Err(e) = parse_public_key(key) {
+                self.error("allowed_public_keys", Some(i), Some(key), e);
+            }
+        }
+
+        if !(MIN_MTU..=MAX_MTU).contains(&config.if_mtu) {
+            let message = format!("{} is out of range, it must be from {} to {}", config.if_mtu, MIN_MTU, MAX_MTU);
+            self.error("if_mtu", None, None, message);
+        }
+
+        for (i, interface) in config.multicast_interfaces.iter().enumerate() {
+            if let Err(e) = regex::Regex::new(&interface.regex) {
+                self.error("multicast_interfaces", Some(i), Some(&interface.regex), format!("invalid regex: {}", e));
+            }
+        }
+
+        if let Err(e) = proto::node_info_json(&config.node_info, config.node_info_privacy) {
+            self.error("node_info", None, None, e);
+        }
+    }
+
+    fn problem(&self, key: &str, index: Option<usize>, value: Option<&str>, message: String) -> Problem {
+        Problem {
+            field: match index {
+                Some(i) => format!("{}[{}]", key, i),
+                None => key.to_string(),
+            },
+            line: self.lines.find(key, index, value),
+            message,
+        }
+    }
+
+    fn error(&mut self, key: &str, index: Option<usize>, value: Option<&str>, message: impl Into<String>) {
+        let problem = self.problem(key, index, value, message.into());
+        self.report.errors.push(problem);
+    }
+
+    fn warning(&mut self, key: &str, message: impl Into<String>) {
+        let problem = self.problem(key, None, None, message.into());
+        self.report.warnings.push(problem);
+    }
+}
+
+/// Finds settings in the file: by the spans of the parsed TOML, or by
+/// searching the text of a yggdrasil-go config.
+struct Lines<'a> {
+    text: &'a str,
+    toml: Option<ImDocument<&'a str>>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Self {
+        let toml = match ConfigFormat::detect(text) {
+            ConfigFormat::Toml => ImDocument::parse(text).ok(),
+            ConfigFormat::Go => None,
+        };
+        Self { text, toml }
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.text[..offset.min(self.text.len())].matches('\n').count() + 1
+    }
+
+    /// Line of the setting `key`, or of its `index`th element, whose text
+    /// is `value`.
+    fn find(&self, key: &str, index: Option<usize>, value: Option<&str>) -> Option<usize> {
+        let Some(doc) = &self.toml else {
+            let start = self.go_key_line(key)?;
+            let found = value.and_then(|value| {
+                self.text
+                    .lines()
+                    .enumerate()
+                    .skip(start)
+                    .find(|(_, line)| line.contains(value))
+            });
+            return Some(found.map_or(start, |(n, _)| n) + 1);
+        };
+        let table = doc.as_table();
+        let element = index.and_then(|i| match table.get(key)? {
+            Item::Value(toml_edit::Value::Array(array)) => array.get(i)?.span(),
+            Item::ArrayOfTables(tables) => tables.get(i)?.span(),
+            _ => None,
+        });
+        let span = element.or_else(|| table.key(key)?.span())?;
+        Some(self.line_of(span.start))
+    }
+
+    /// The top-level TOML setting at `offset`.
+    fn field_at(&self, offset: usize) -> Option<String> {
+        let table = self.toml.as_ref()?.as_table();
+        table
+            .iter()
+            .find(|(key, item)| {
+                let key_span = table.key(key).and_then(|k| k.span());
+                [key_span, item.span()].into_iter().flatten().any(|span| span.contains(&offset))
+            })
+            .map(|(key, _)| key.to_string())
+    }
+
+    /// Index of the line where a yggdrasil-go setting is, matching `key`
+    /// without case and underscores (`if_mtu` finds `IfMTU`).
+    fn go_key_line(&self, key: &str) -> Option<usize> {
+        let name = key.replace('_', "").to_lowercase();
+        self.text.lines().position(|line| {
+            let line = line.to_lowercase();
+            line.match_indices(&name).any(|(at, _)| {
+                let before = line[..at].chars().next_back();
+                let after = line[at + name.len()..].trim_start_matches(['"', '\'']).trim_start();
+                !before.is_some_and(|c| c.is_alphanumeric()) && after.starts_with(':')
+            })
+        })
+    }
+
+    /// A yggdrasil-go import message, `Key: message` or `Key[0].Field:
+    /// message`, as a problem with the key's line.
+    fn go_problem(&self, message: String) -> Problem {
+        if message.starts_with("line ") {
+            return Problem { field: String::new(), line: None, message };
+        }
+        let (field, rest) = message.split_once(": ").unwrap_or(("", &message));
+        let key = field.split(['.', '[']).next().unwrap_or_default();
+        Problem {
+            field: field.to_string(),
+            line: self.go_key_line(key).map(|n| n + 1),
+            message: rest.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_toml() {
+        let config = Config::generate_config_text()
+            .replace(
+                "peers = []",
+                "peers = [\n  \"tcp://192.0.2.1:9001\",\n  \"tls://192.0.2.2:443?key=00\",\n  \"tcp://192.0.2.3\",\n]",
+            )
+            .replace("if_mtu = 65535", "if_mtu = 100")
+            .replace("if_name =", &format!("allowed_public_keys = [\"{}\", \"xyz\"]\nif_name =", "00".repeat(32)));
+        let line = |needle: &str| config.lines().position(|l| l.contains(needle)).unwrap() + 1;
+
+        let report = check(&config);
+        let errors: Vec<_> = report.errors.iter().map(|p| (p.field.as_str(), p.line)).collect();
+        assert_eq!(
+            errors,
+            [
+                ("peers[1]", Some(line("192.0.2.2"))),
+                ("peers[2]", Some(line("192.0.2.3"))),
+                ("allowed_public_keys[1]", Some(line("xyz"))),
+                ("if_mtu", Some(line("if_mtu ="))),
+            ]
+        );
+        assert_eq!(report.errors[0].message, "pinned key must be 32 bytes");
+        assert!(report.errors[3].to_string().starts_with(&format!("line {}: if_mtu: 100 is out of range", line("if_mtu ="))));
+        assert!(report.warnings.is_empty());
+
+        assert!(check(&Config::generate_config_text()).errors.is_empty());
+
+        // Type errors name the setting
+        let report = check("peers = []\nif_mtu = \"big\"\n");
+        assert_eq!(report.errors[0].field, "if_mtu");
+        assert_eq!(report.errors[0].line, Some(2));
+
+        let report = check(
+            "listen = [\n  \"socks://127.0.0.1:1080/x:1\",\n  \"tcp://[::]\",\n  \"ws://[::]\",\n]\nadmin_listen = \"localhost:9001\"\n",
+        );
+        let errors: Vec<_> = report.errors.iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "line 2: listen[0]: socks can only be used for outbound peers",
+                // listen would not know which port to take; ws has port 80
+                "line 3: listen[1]: missing port, use :0 for a random one",
+                "line 6: admin_listen: admin listen must start with tcp:// or unix://, got: localhost:9001",
+            ]
+        );
+        assert_eq!(report.warnings[0].field, "private_key");
+    }
+
+    #[test]
+    fn test_check_go() {
+        let config = "{\n  PrivateKey: 00\n  Peers: [\n    tcp://192.0.2.1:9001\n    bogus\n  ]\n  IfMTU: 65535\n  LogLookups: false\n}\n";
+        let report = check(config);
+        let errors: Vec<_> = report.errors.iter().map(|p| p.to_string()).collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("line 2: private_key: "), "{}", errors[0]);
+        assert!(errors[1].starts_with("line 5: peers[1]: invalid URI"), "{}", errors[1]);
+        assert_eq!(report.warnings[0].to_string(), "line 8: LogLookups: not supported, ignored");
+
+        let report = check("{\n  Peers: [\n  ]\n  IfMTU: lots\n}\n");
+        assert_eq!(report.errors[0].to_string(), "line 4: IfMTU: expected a number");
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use ed25519_dalek::SigningKey;
 use serde::{Deserialize, Serialize};
 
@@ -20,6 +22,27 @@ pub struct Config {
     #[serde(default)]
     pub admin_listen: String,
 
+    /// Credentials for the admin socket. Without any, every client has
+    /// control access.
+    #[serde(default)]
+    pub admin_credentials: Vec<AdminCredential>,
+
+    /// Let `addPeer` add `exec:`, `pipe:` and `serial:` peers, which
+    /// run a command or open a device as the user the node runs as.
+    /// Otherwise those can only be set in this file.
+    #[serde(default)]
+    pub admin_allow_local_peers: bool,
+
+    /// Write peers and listeners added or removed over the admin socket
+    /// back to the config file, as if every call passed `persist=true`.
+    #[serde(default)]
+    pub persist_admin_changes: bool,
+
+    /// Prometheus metrics address, e.g. `"127.0.0.1:9464"`. Empty disables
+    /// the endpoint.
+    #[serde(default)]
+    pub metrics_listen: String,
+
     /// TUN interface name. "auto" for auto-name, "none" to disable.
     #[serde(default = "default_if_name")]
     pub if_name: String,
@@ -39,6 +62,103 @@ pub struct Config {
     /// If non-empty, only allow peering with these public keys (hex).
     #[serde(default)]
     pub allowed_public_keys: Vec<String>,
+
+    /// End-to-end traffic protection: `"encrypted"` (default) or `"signed"`.
+    #[serde(default)]
+    pub crypto_mode: CryptoMode,
+
+    /// Multicast peer discovery per interface. Empty (the default) disables
+    /// discovery; generated configs enable it on every interface.
+    #[serde(default)]
+    pub multicast_interfaces: Vec<MulticastInterfaceConfig>,
+}
+
+/// The formats a config file can be in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    /// yggdrasil-go's HJSON or JSON.
+    Go,
+}
+
+impl ConfigFormat {
+    /// Tell the formats apart by the first thing in the file: HJSON starts
+    /// with `{`, a `//` or `/*` comment, or `Key:`, none of which is TOML.
+    pub fn detect(text: &str) -> Self {
+        let start = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+            .unwrap_or_default();
+        let key_end = start.find(|c: char| c.is_whitespace() || c == '=' || c == ':').unwrap_or(start.len());
+        let go_key = !start.starts_with(['"', '\'']) && start[key_end..].trim_start().starts_with(':');
+        if start.starts_with(['{', '/']) || go_key {
+            ConfigFormat::Go
+        } else {
+            ConfigFormat::Toml
+        }
+    }
+}
+
+/// Multicast discovery settings for interfaces whose name matches `regex`.
+/// The first matching entry applies.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MulticastInterfaceConfig {
+    /// Interface name regex, e.g. `"eth.*"`.
+    pub regex: String,
+    /// Advertise this node with beacons and accept links on the interface.
+    #[serde(default = "default_true")]
+    pub beacon: bool,
+    /// Dial nodes whose beacons are heard on the interface.
+    #[serde(default = "default_true")]
+    pub listen: bool,
+    /// TLS listener port (0 = random).
+    #[serde(default)]
+    pub port: u16,
+    /// Link priority for discovered peers.
+    #[serde(default)]
+    pub priority: u8,
+    /// Only nodes with the same password discover each other.
+    #[serde(default)]
+    pub password: String,
+}
+
+/// One admin socket credential: a shared `token`, or the hex Ed25519 public
+/// `key` of a client that signs its requests.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AdminCredential {
+    #[serde(default)]
+    pub token: String,
+    #[serde(default)]
+    pub key: String,
+    /// What requests with this credential may do.
+    #[serde(default)]
+    pub access: AdminAccess,
+}
+
+/// Permission level of an admin client. Control includes read access.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminAccess {
+    /// Query calls (`get*`, `crawl`, `subscribe`, ...).
+    #[default]
+    Read,
+    /// Calls that change the node: `addPeer`, `removePeer`, `addListener`,
+    /// `removeListener` and `reloadConfig`.
+    Control,
+}
+
+/// How ironwood protects traffic between nodes.
+///
+/// Signed mode authenticates packets but sends them in the clear, for
+/// networks where encryption is not allowed (e.g. amateur radio). Nodes
+/// only understand each other when they use the same mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CryptoMode {
+    #[default]
+    Encrypted,
+    Signed,
 }
 
 fn default_if_name() -> String {
@@ -49,6 +169,10 @@ fn default_mtu() -> u64 {
     65535
 }
 
+fn default_true() -> bool {
+    true
+}
+
 fn default_node_info() -> toml::Value {
     toml::Value::Table(toml::map::Map::new())
 }
@@ -60,15 +184,45 @@ impl Default for Config {
             peers: Vec::new(),
             listen: vec!["tcp://[::]:0".to_string()],
             admin_listen: "tcp://localhost:9001".to_string(),
+            admin_credentials: Vec::new(),
+            admin_allow_local_peers: false,
+            persist_admin_changes: false,
+            metrics_listen: String::new(),
             if_name: default_if_name(),
             if_mtu: default_mtu(),
             node_info: toml::Value::Table(toml::map::Map::new()),
             node_info_privacy: false,
             allowed_public_keys: Vec::new(),
+            crypto_mode: CryptoMode::default(),
+            multicast_interfaces: Vec::new(),
         }
     }
 }
 
+/// Parse an Ed25519 private key given as 128 hex chars (secret key followed
+/// by public key, as in `private_key`).
+pub fn parse_private_key(hex_key: &str) -> Result<SigningKey, String> {
+    let bytes = hex::decode(hex_key.trim())
+        .map_err(|e| format!("invalid private key hex: {}", e))?;
+    if bytes.len() != 64 {
+        return Err(format!(
+            "private key should be 64 bytes, got {}",
+            bytes.len()
+        ));
+    }
+    let key_bytes: [u8; 64] = bytes.try_into().unwrap();
+    SigningKey::from_keypair_bytes(&key_bytes)
+        .map_err(|e| format!("invalid ed25519 key: {}", e))
+}
+
+/// Parse an Ed25519 public key given as 64 hex chars.
+pub fn parse_public_key(hex_key: &str) -> Result<[u8; 32], String> {
+    let bytes = hex::decode(hex_key).map_err(|e| format!("invalid hex: {}", e))?;
+    bytes
+        .try_into()
+        .map_err(|v: Vec<u8>| format!("public key should be 32 bytes, got {}", v.len()))
+}
+
 const CONFIG_TEMPLATE: &str = include_str!("config_template.toml");
 
 impl Config {
@@ -88,37 +242,120 @@ impl Config {
         CONFIG_TEMPLATE.replace("{{PRIVATE_KEY}}", &key_hex)
     }
 
+    /// Read a config file, TOML or yggdrasil-go, and log the warnings.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let (config, warnings) = Self::read(path)?;
+        for warning in warnings {
+            tracing::warn!("{}: {}", path.display(), warning);
+        }
+        Ok(config)
+    }
+
+    /// Read a config file, TOML or yggdrasil-go, returning the warnings
+    /// about yggdrasil-go settings that were left out.
+    pub fn read(path: &Path) -> Result<(Self, Vec<String>), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse a config in either format.
+    pub fn parse(text: &str) -> Result<(Self, Vec<String>), String> {
+        match ConfigFormat::detect(text) {
+            ConfigFormat::Toml => toml::from_str(text).map(|config| (config, Vec::new())).map_err(|e| e.to_string()),
+            ConfigFormat::Go => crate::go_config::import(text),
+        }
+    }
+
+    /// The config as TOML, for `--normaliseconf`.
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| e.to_string())
+    }
+
+    /// Put back the settings that only take effect at startup where they
+    /// differ from the `running` config, and return their names.
+    pub fn keep_startup_settings(&mut self, running: &Config) -> Vec<&'static str> {
+        fn keep<T: Clone + PartialEq>(name: &'static str, new: &mut T, old: &T, changed: &mut Vec<&'static str>) {
+            if new != old {
+                *new = old.clone();
+                changed.push(name);
+            }
+        }
+        let mut changed = Vec::new();
+        keep("private_key", &mut self.private_key, &running.private_key, &mut changed);
+        keep("admin_listen", &mut self.admin_listen, &running.admin_listen, &mut changed);
+        keep("admin_credentials", &mut self.admin_credentials, &running.admin_credentials, &mut changed);
+        keep("metrics_listen", &mut self.metrics_listen, &running.metrics_listen, &mut changed);
+        keep("if_name", &mut self.if_name, &running.if_name, &mut changed);
+        keep("if_mtu", &mut self.if_mtu, &running.if_mtu, &mut changed);
+        keep("crypto_mode", &mut self.crypto_mode, &running.crypto_mode, &mut changed);
+        keep("multicast_interfaces", &mut self.multicast_interfaces, &running.multicast_interfaces, &mut changed);
+        changed
+    }
+
     /// Parse the private key from hex.
     pub fn signing_key(&self) -> Result<SigningKey, String> {
         if self.private_key.is_empty() {
             return Err("no private key configured".to_string());
         }
-        let bytes = hex::decode(&self.private_key)
-            .map_err(|e| format!("invalid private key hex: {}", e))?;
-        if bytes.len() != 64 {
-            return Err(format!(
-                "private key should be 64 bytes, got {}",
-                bytes.len()
-            ));
-        }
-        let key_bytes: [u8; 64] = bytes.try_into().unwrap();
-        SigningKey::from_keypair_bytes(&key_bytes)
-            .map_err(|e| format!("invalid ed25519 key: {}", e))
+        parse_private_key(&self.private_key)
     }
 
-    /// Get the set of allowed public keys (parsed from hex).
+    /// Get the set of allowed public keys (parsed from hex). Malformed
+    /// entries are skipped; `--check-config` reports them.
     pub fn allowed_keys(&self) -> Vec<[u8; 32]> {
         self.allowed_public_keys
             .iter()
-            .filter_map(|s| {
-                let bytes = hex::decode(s).ok()?;
-                if bytes.len() != 32 {
-                    return None;
-                }
-                let mut arr = [0u8; 32];
-                arr.copy_from_slice(&bytes);
-                Some(arr)
-            })
+            .filter_map(|s| parse_public_key(s).ok())
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keep_startup_settings() {
+        let running = Config::generate();
+        let mut new = running.clone();
+        assert!(new.keep_startup_settings(&running).is_empty());
+
+        new.private_key = Config::generate().private_key;
+        new.if_name = "ygg1".to_string();
+        new.peers.push("tcp://192.0.2.1:9001".to_string());
+        assert_eq!(new.keep_startup_settings(&running), ["private_key", "if_name"]);
+        assert_eq!(new.private_key, running.private_key);
+        assert_eq!(new.if_name, running.if_name);
+        assert_eq!(new.peers.len(), running.peers.len() + 1);
+    }
+
+    #[test]
+    fn test_multicast_default() {
+        // Discovery is only on where the config asks for it
+        let config: Config = toml::from_str("peers = []").unwrap();
+        assert!(config.multicast_interfaces.is_empty());
+        assert!(Config::default().multicast_interfaces.is_empty());
+        assert_eq!(Config::generate().multicast_interfaces[0].regex, ".*");
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(ConfigFormat::detect(&Config::generate_config_text()), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::detect("# comment\n\n{\n  Peers: []\n}\n"), ConfigFormat::Go);
+        assert_eq!(ConfigFormat::detect("// comment\nPeers: []\n"), ConfigFormat::Go);
+        assert_eq!(ConfigFormat::detect("PrivateKey: abc\n"), ConfigFormat::Go);
+        assert_eq!(ConfigFormat::detect("\"peers\" = []\n"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::detect("\"a:b\" = 1\n"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::detect(""), ConfigFormat::Toml);
+
+        let (config, warnings) = Config::parse("{\"IfMTU\": 1280, \"Peers\": [\"tcp://192.0.2.1:9001\"]}").unwrap();
+        assert_eq!((config.if_mtu, config.peers.len()), (1280, 1));
+        assert!(warnings.is_empty());
+
+        // The normalised TOML reads back as the same config
+        let config = Config::generate();
+        let (again, _) = Config::parse(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(again.to_toml().unwrap(), config.to_toml().unwrap());
+        assert_eq!(again.multicast_interfaces, config.multicast_interfaces);
+    }
+}
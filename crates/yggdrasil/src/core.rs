@@ -1,36 +1,147 @@
-use std::collections::HashSet;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use ed25519_dalek::SigningKey;
-use ironwood::{Addr, Config as IwConfig, EncryptedPacketConn, PacketConn};
-use tokio::sync::Mutex;
+use ironwood::{Addr, Config as IwConfig, EncryptedPacketConn, PacketConn, SignedPacketConn};
+use serde::Serialize;
+use tokio::sync::{mpsc, Mutex};
 
 use crate::address::{addr_for_key, subnet_for_key, Address, Subnet};
-use crate::config::Config;
+use crate::config::{Config, CryptoMode};
+use crate::events::{Event, Events, NodeRef};
 use crate::ipv6rwc::ReadWriteCloser;
-use crate::links::{ActiveLinks, Links, LinkPeerInfo};
+use crate::links::{self, ActiveLinks, Links, LinkPeerInfo};
+use crate::persist::{self, ListEdit};
+use crate::proto::{self, ProtoHandler};
 
 /// Session type byte prefixed to ironwood payloads.
 const TYPE_SESSION_TRAFFIC: u8 = 0x01;
 const TYPE_SESSION_PROTO: u8 = 0x02;
 
+/// How long to wait for a remote node to answer a protocol request.
+const PROTO_REQUEST_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// Remote NodeInfo and debug requests waiting to be answered. More are
+/// dropped rather than queued without bound.
+const PROTO_REQUEST_QUEUE: usize = 64;
+
 /// Shared slot for path_notify callback target.
 /// Filled in after Core and RWC are both created.
 pub type PathNotifySlot = Arc<std::sync::Mutex<Option<Arc<ReadWriteCloser>>>>;
 
-/// Core wraps an ironwood EncryptedPacketConn with session type handling
-/// and TCP link management.
+/// The ironwood PacketConn selected by `crypto_mode`.
+pub(crate) enum Ironwood {
+    Encrypted(Arc<EncryptedPacketConn>),
+    Signed(Arc<SignedPacketConn>),
+}
+
+impl Ironwood {
+    /// The connection as a plain PacketConn.
+    pub(crate) fn conn(&self) -> &dyn PacketConn {
+        match self {
+            Ironwood::Encrypted(c) => c.as_ref(),
+            Ironwood::Signed(c) => c.as_ref(),
+        }
+    }
+
+    async fn get_peers(&self) -> Vec<ironwood::PeerInfo> {
+        match self {
+            Ironwood::Encrypted(c) => c.get_peers().await,
+            Ironwood::Signed(c) => c.get_peers().await,
+        }
+    }
+
+    async fn get_tree(&self) -> Vec<ironwood::TreeEntry> {
+        match self {
+            Ironwood::Encrypted(c) => c.get_tree().await,
+            Ironwood::Signed(c) => c.get_tree().await,
+        }
+    }
+
+    async fn get_paths(&self) -> Vec<ironwood::PathEntry> {
+        match self {
+            Ironwood::Encrypted(c) => c.get_paths().await,
+            Ironwood::Signed(c) => c.get_paths().await,
+        }
+    }
+
+    /// Encrypted sessions; there are none in signed mode.
+    async fn get_sessions(&self) -> Vec<ironwood::SessionEntry> {
+        match self {
+            Ironwood::Encrypted(c) => c.get_sessions().await,
+            Ironwood::Signed(_) => Vec::new(),
+        }
+    }
+
+    async fn routing_entries(&self) -> usize {
+        match self {
+            Ironwood::Encrypted(c) => c.routing_entries().await,
+            Ironwood::Signed(c) => c.routing_entries().await,
+        }
+    }
+
+    async fn tree_coordinates(&self) -> Vec<u64> {
+        match self {
+            Ironwood::Encrypted(c) => c.tree_coordinates().await,
+            Ironwood::Signed(c) => c.tree_coordinates().await,
+        }
+    }
+
+    async fn queue_drops(&self) -> ironwood::QueueDrops {
+        match self {
+            Ironwood::Encrypted(c) => c.queue_drops().await,
+            Ironwood::Signed(c) => c.queue_drops().await,
+        }
+    }
+}
+
+/// What a config reload changed.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ReloadReport {
+    pub peers_added: Vec<String>,
+    pub peers_removed: Vec<String>,
+    pub listeners_added: Vec<String>,
+    pub listeners_removed: Vec<String>,
+    /// Applies to links set up from now on; inbound links from keys that
+    /// are no longer allowed are closed (`links_closed`).
+    pub allowed_public_keys_changed: bool,
+    /// Inbound links closed as their key is no longer allowed, as
+    /// "address @ remote".
+    pub links_closed: Vec<String>,
+    pub node_info_changed: bool,
+    /// Changed settings that only take effect after a restart.
+    pub restart_required: Vec<String>,
+    /// Changes that could not be applied.
+    pub errors: Vec<String>,
+}
+
+/// Entries of `from` that are not in `other`.
+fn difference<'a>(from: &'a [String], other: &'a [String]) -> impl Iterator<Item = &'a String> {
+    from.iter().filter(move |item| !other.contains(item))
+}
+
+/// Core wraps an ironwood PacketConn (encrypted or signed) with session
+/// type handling and link management.
 pub struct Core {
-    pub(crate) inner: Arc<EncryptedPacketConn>,
+    pub(crate) inner: Ironwood,
     pub(crate) links: Mutex<Links>,
     pub(crate) active_links: ActiveLinks,
     pub(crate) signing_key: SigningKey,
     pub(crate) public_key: [u8; 32],
     pub(crate) address: Address,
     pub(crate) subnet: Subnet,
-    pub(crate) allowed_keys: HashSet<[u8; 32]>,
-    pub(crate) config: Config,
+    pub(crate) allowed_keys: RwLock<HashSet<[u8; 32]>>,
+    /// The config as currently applied; changed by `reload`.
+    pub(crate) config: RwLock<Config>,
+    /// File the config was read from, for `reload_config`.
+    config_path: std::sync::Mutex<Option<PathBuf>>,
     pub(crate) path_notify_slot: PathNotifySlot,
+    pub(crate) proto: ProtoHandler,
+    /// Remote requests for `answer_proto_requests`.
+    proto_requests: mpsc::Sender<(Addr, Vec<u8>)>,
+    pub(crate) events: Events,
 }
 
 impl Core {
@@ -47,8 +158,13 @@ impl Core {
         // Create a shared slot for the path_notify target
         let path_notify_slot: PathNotifySlot = Arc::new(std::sync::Mutex::new(None));
         let slot_clone = path_notify_slot.clone();
+        let events = Events::new();
+        let path_events = events.clone();
+        let tree_events = events.clone();
+        let session_events = events.clone();
 
-        // Create ironwood config with bloom transform and path notify
+        // Create ironwood config with bloom transform and the notify
+        // callbacks feeding path updates and events
         let iw_config = IwConfig::default()
             .with_bloom_transform(|key: [u8; 32]| -> [u8; 32] {
                 let subnet = subnet_for_key(&key);
@@ -56,6 +172,7 @@ impl Core {
             })
             .with_peer_max_message_size(65535 * 2)
             .with_path_notify(move |key: [u8; 32]| {
+                path_events.emit(Event::PathNotify { node: NodeRef::new(&key) });
                 let rwc = {
                     let guard = slot_clone.lock().unwrap();
                     guard.clone()
@@ -65,11 +182,37 @@ impl Core {
                         rwc.update_key(key).await;
                     });
                 }
+            })
+            .with_tree_notify(move |root: [u8; 32], parent: [u8; 32]| {
+                tree_events.emit(Event::TreeChanged { root: hex::encode(root), parent: hex::encode(parent) });
+            })
+            .with_session_notify(move |key: [u8; 32], established: bool| {
+                let node = NodeRef::new(&key);
+                session_events.emit(if established {
+                    Event::SessionEstablished { node }
+                } else {
+                    Event::SessionExpired { node }
+                });
             });
 
-        let inner = ironwood::new_encrypted_packet_conn(signing_key.clone(), iw_config);
+        let inner = match config.crypto_mode {
+            CryptoMode::Encrypted => Ironwood::Encrypted(ironwood::new_encrypted_packet_conn(
+                signing_key.clone(),
+                iw_config,
+            )),
+            CryptoMode::Signed => {
+                tracing::warn!("Signed crypto mode: traffic is authenticated but NOT encrypted");
+                Ironwood::Signed(ironwood::new_signed_packet_conn(signing_key.clone(), iw_config))
+            }
+        };
+
+        let node_info = proto::node_info_json(&config.node_info, config.node_info_privacy).unwrap_or_else(|e| {
+            tracing::error!("Not serving NodeInfo: {}", e);
+            b"{}".to_vec()
+        });
 
         let active_links = ActiveLinks::new();
+        let (proto_requests, proto_requests_rx) = mpsc::channel(PROTO_REQUEST_QUEUE);
 
         let core = Arc::new(Self {
             inner,
@@ -79,10 +222,15 @@ impl Core {
             public_key,
             address,
             subnet,
-            allowed_keys,
-            config,
+            allowed_keys: RwLock::new(allowed_keys),
+            config: RwLock::new(config),
+            config_path: std::sync::Mutex::new(None),
             path_notify_slot,
+            proto: ProtoHandler::new(node_info),
+            proto_requests,
+            events,
         });
+        tokio::spawn(answer_proto_requests(Arc::downgrade(&core), proto_requests_rx));
 
         core
     }
@@ -98,7 +246,7 @@ impl Core {
     pub async fn read_from(&self, buf: &mut [u8]) -> Result<(usize, Addr), ironwood::Error> {
         loop {
             let mut inner_buf = vec![0u8; buf.len() + 1];
-            let (n, addr) = self.inner.read_from(&mut inner_buf).await?;
+            let (n, addr) = self.inner.conn().read_from(&mut inner_buf).await?;
             tracing::debug!("Core read: {n} bytes with {} from {}", inner_buf[0], &addr);
             if n == 0 {
                 continue;
@@ -110,13 +258,8 @@ impl Core {
                     return Ok((payload_len, addr));
                 }
                 TYPE_SESSION_PROTO => {
-                    // TODO implement real handling
-                    let key = addr; // The Addr contains the public key
-                    let payload_len = n - 1;
-                    buf[..payload_len].copy_from_slice(&inner_buf[1..n]);
-                    return Ok((payload_len, addr));
-
-                    //continue;
+                    self.handle_proto(addr, &inner_buf[1..n]);
+                    continue;
                 }
                 _ => {
                     continue;
@@ -130,7 +273,7 @@ impl Core {
         let mut payload = Vec::with_capacity(1 + buf.len());
         payload.push(TYPE_SESSION_TRAFFIC);
         payload.extend_from_slice(buf);
-        let n = self.inner.write_to(&payload, addr).await?;
+        let n = self.inner.conn().write_to(&payload, addr).await?;
         if n > 0 {
             Ok(n - 1)
         } else {
@@ -138,14 +281,137 @@ impl Core {
         }
     }
 
+    /// Handle a session protocol packet from `from`. Responses to our
+    /// requests are handed over at once; requests are queued for
+    /// `answer_proto_requests`, so that writing the answer never holds up
+    /// the traffic read after them.
+    fn handle_proto(&self, from: Addr, data: &[u8]) {
+        match data {
+            [proto::TYPE_PROTO_NODEINFO_RESPONSE, info @ ..] => self.proto.node_info_waiters.deliver(from.0, info),
+            [proto::TYPE_PROTO_DEBUG, kind, response @ ..] => match self.proto.debug_waiters(*kind) {
+                Some(waiters) => waiters.deliver(from.0, response),
+                None => self.queue_proto_request(from, data),
+            },
+            [proto::TYPE_PROTO_NODEINFO_REQUEST, ..] => self.queue_proto_request(from, data),
+            _ => {}
+        }
+    }
+
+    fn queue_proto_request(&self, from: Addr, request: &[u8]) {
+        if self.proto_requests.try_send((from, request.to_vec())).is_err() {
+            tracing::debug!("Dropped protocol request from {}: too many waiting", from);
+        }
+    }
+
+    /// Answer a remote NodeInfo or debug request.
+    async fn answer_proto(&self, from: Addr, request: &[u8]) {
+        let response = match request {
+            [proto::TYPE_PROTO_NODEINFO_REQUEST, ..] => self.proto.node_info_response(),
+            [proto::TYPE_PROTO_DEBUG, kind, ..] => match self.debug_response(*kind).await {
+                Some(response) => response,
+                None => return,
+            },
+            _ => return,
+        };
+        match tokio::time::timeout(PROTO_REQUEST_TIMEOUT, self.write_proto(&response, &from)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => tracing::debug!("Failed to answer protocol request from {}: {}", from, e),
+            Err(_) => tracing::debug!("Answering protocol request from {} timed out", from),
+        }
+    }
+
+    /// Our answer to a remote debug request of type `kind`.
+    async fn debug_response(&self, kind: u8) -> Option<Vec<u8>> {
+        Some(match kind {
+            proto::TYPE_DEBUG_GET_SELF_REQUEST => {
+                let info = serde_json::json!({
+                    "key": hex::encode(self.public_key),
+                    "routing_entries": self.routing_entries().await.to_string(),
+                });
+                let mut response = vec![proto::TYPE_PROTO_DEBUG, proto::TYPE_DEBUG_GET_SELF_RESPONSE];
+                response.extend_from_slice(info.to_string().as_bytes());
+                response
+            }
+            proto::TYPE_DEBUG_GET_PEERS_REQUEST => {
+                let keys = self.peer_keys().await;
+                proto::debug_keys_response(proto::TYPE_DEBUG_GET_PEERS_RESPONSE, keys, self.mtu())
+            }
+            proto::TYPE_DEBUG_GET_TREE_REQUEST => {
+                let keys = self.get_tree().await.into_iter().map(|t| t.key);
+                proto::debug_keys_response(proto::TYPE_DEBUG_GET_TREE_RESPONSE, keys, self.mtu())
+            }
+            _ => return None,
+        })
+    }
+
+    /// Write a session protocol packet to ironwood.
+    async fn write_proto(&self, data: &[u8], addr: &Addr) -> Result<usize, ironwood::Error> {
+        let mut payload = Vec::with_capacity(1 + data.len());
+        payload.push(TYPE_SESSION_PROTO);
+        payload.extend_from_slice(data);
+        self.inner.conn().write_to(&payload, addr).await
+    }
+
+    /// Send a protocol request to `key` and wait for the answer that
+    /// `waiters` is delivered.
+    async fn proto_request(&self, key: [u8; 32], request: &[u8], waiters: &proto::Waiters) -> Result<Vec<u8>, String> {
+        let response = waiters.wait(key);
+        self.write_proto(request, &Addr(key))
+            .await
+            .map_err(|e| format!("failed to send request: {}", e))?;
+        tokio::time::timeout(PROTO_REQUEST_TIMEOUT, response)
+            .await
+            .map_err(|_| "timed out waiting for response".to_string())?
+            .map_err(|_| "request cancelled".to_string())
+    }
+
+    /// Ask the node with `key` for its NodeInfo and wait for the answer.
+    pub async fn get_node_info(&self, key: [u8; 32]) -> Result<serde_json::Value, String> {
+        let info = self
+            .proto_request(key, &[proto::TYPE_PROTO_NODEINFO_REQUEST], &self.proto.node_info_waiters)
+            .await?;
+        serde_json::from_slice(&info).map_err(|e| format!("invalid NodeInfo: {}", e))
+    }
+
+    /// Ask the node with `key` about itself (remote debug GetSelf).
+    pub async fn debug_remote_get_self(&self, key: [u8; 32]) -> Result<serde_json::Value, String> {
+        let request = [proto::TYPE_PROTO_DEBUG, proto::TYPE_DEBUG_GET_SELF_REQUEST];
+        let info = self.proto_request(key, &request, &self.proto.self_waiters).await?;
+        serde_json::from_slice(&info).map_err(|e| format!("invalid response: {}", e))
+    }
+
+    /// Ask the node with `key` for the keys of its peers.
+    pub async fn debug_remote_get_peers(&self, key: [u8; 32]) -> Result<Vec<[u8; 32]>, String> {
+        let request = [proto::TYPE_PROTO_DEBUG, proto::TYPE_DEBUG_GET_PEERS_REQUEST];
+        let keys = self.proto_request(key, &request, &self.proto.peers_waiters).await?;
+        Ok(proto::parse_keys(&keys))
+    }
+
+    /// Ask the node with `key` for the keys in its view of the tree.
+    pub async fn debug_remote_get_tree(&self, key: [u8; 32]) -> Result<Vec<[u8; 32]>, String> {
+        let request = [proto::TYPE_PROTO_DEBUG, proto::TYPE_DEBUG_GET_TREE_REQUEST];
+        let keys = self.proto_request(key, &request, &self.proto.tree_waiters).await?;
+        Ok(proto::parse_keys(&keys))
+    }
+
+    /// Our own NodeInfo, as other nodes receive it.
+    pub fn node_info(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.proto.node_info()).unwrap_or_default()
+    }
+
+    /// The node's event channel (admin `subscribe`).
+    pub fn events(&self) -> &Events {
+        &self.events
+    }
+
     /// Send a key lookup via ironwood.
     pub async fn send_lookup(&self, target: Addr) {
-        self.inner.send_lookup(target).await;
+        self.inner.conn().send_lookup(target).await;
     }
 
     /// Get the MTU (ironwood MTU minus session type overhead, capped at 65535).
     pub fn mtu(&self) -> u64 {
-        let m = self.inner.mtu().saturating_sub(1);
+        let m = self.inner.conn().mtu().saturating_sub(1);
         m.min(65535)
     }
 
@@ -166,10 +432,8 @@ impl Core {
 
     /// Check if a public key is allowed to connect.
     pub fn is_key_allowed(&self, key: &[u8; 32]) -> bool {
-        if self.allowed_keys.is_empty() {
-            return true;
-        }
-        self.allowed_keys.contains(key)
+        let allowed_keys = self.allowed_keys.read().unwrap();
+        allowed_keys.is_empty() || allowed_keys.contains(key)
     }
 
     /// Handle a new peer connection (delegate to ironwood).
@@ -178,8 +442,9 @@ impl Core {
         key: [u8; 32],
         conn: Box<dyn ironwood::types::AsyncConn>,
         priority: u8,
+        link_id: u64,
     ) -> Result<(), ironwood::Error> {
-        self.inner.handle_conn(Addr(key), conn, priority).await
+        self.inner.conn().handle_conn(Addr(key), conn, priority, link_id).await
     }
 
     /// Initialize the links with a reference to this core.
@@ -194,12 +459,12 @@ impl Core {
             let mut links = self.links.lock().await;
             links.close().await;
         }
-        self.inner.close().await
+        self.inner.conn().close().await
     }
 
     /// Start listeners and connect to configured peers.
     pub async fn start(self: &Arc<Self>) {
-        let config = self.config.clone();
+        let config = self.config();
 
         for addr in &config.listen {
             if let Err(e) = self.listen(addr).await {
@@ -214,12 +479,136 @@ impl Core {
         }
     }
 
+    /// The config as currently applied.
+    pub fn config(&self) -> Config {
+        self.config.read().unwrap().clone()
+    }
+
+    /// Remember the file the config was read from, so that `reload_config`
+    /// can read it again.
+    pub fn set_config_path(&self, path: PathBuf) {
+        *self.config_path.lock().unwrap() = Some(path);
+    }
+
+    /// Read the config file again and apply it (SIGHUP, admin
+    /// `reloadConfig`).
+    pub async fn reload_config(&self) -> Result<ReloadReport, String> {
+        let path = self
+            .config_path
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or("no config file to reload")?;
+        let config = Config::load(&path)?;
+        Ok(self.reload(config).await)
+    }
+
+    /// Apply a new config to the running node. Peers and listeners are
+    /// diffed against the previous config, so peers added over the admin
+    /// socket stay. Settings that need a restart are reported and left as
+    /// they are.
+    pub async fn reload(&self, mut new: Config) -> ReloadReport {
+        let old = self.config();
+        let mut report = ReloadReport {
+            restart_required: new.keep_startup_settings(&old).into_iter().map(String::from).collect(),
+            ..Default::default()
+        };
+
+        {
+            let mut links = self.links.lock().await;
+            for addr in difference(&old.listen, &new.listen) {
+                links.stop_listener(addr);
+                report.listeners_removed.push(addr.clone());
+            }
+            for addr in difference(&new.listen, &old.listen) {
+                match links.listen(addr).await {
+                    Ok(()) => report.listeners_added.push(addr.clone()),
+                    Err(e) => report.errors.push(format!("listen {}: {}", addr, e)),
+                }
+            }
+            for uri in difference(&old.peers, &new.peers) {
+                // Already gone if it was removed over the admin socket
+                let _ = links.remove_peer(uri).await;
+                report.peers_removed.push(uri.clone());
+            }
+            for uri in difference(&new.peers, &old.peers) {
+                match links.add_peer(uri).await {
+                    Ok(()) => report.peers_added.push(uri.clone()),
+                    Err(e) => report.errors.push(format!("peer {}: {}", uri, e)),
+                }
+            }
+        }
+        // Failed additions are left out, so the next reload tries again
+        new.listen.retain(|a| old.listen.contains(a) || report.listeners_added.contains(a));
+        new.peers.retain(|p| old.peers.contains(p) || report.peers_added.contains(p));
+
+        if new.allowed_public_keys != old.allowed_public_keys {
+            *self.allowed_keys.write().unwrap() = new.allowed_keys().into_iter().collect();
+            report.allowed_public_keys_changed = true;
+            for (key, peer) in self.active_links.close_inbound(|key| self.is_key_allowed(key)).await {
+                let link = format!("{} @ {}", crate::address::addr_for_key(&key), peer);
+                tracing::info!("Closing inbound link {}: key no longer allowed", link);
+                report.links_closed.push(link);
+            }
+        }
+
+        if new.node_info != old.node_info || new.node_info_privacy != old.node_info_privacy {
+            match proto::node_info_json(&new.node_info, new.node_info_privacy) {
+                Ok(info) => {
+                    self.proto.set_node_info(info);
+                    report.node_info_changed = true;
+                }
+                Err(e) => {
+                    report.errors.push(e);
+                    new.node_info = old.node_info.clone();
+                    new.node_info_privacy = old.node_info_privacy;
+                }
+            }
+        }
+
+        *self.config.write().unwrap() = new;
+        report
+    }
+
     /// Start listening on the given address.
     pub async fn listen(&self, addr: &str) -> Result<(), String> {
         let mut links = self.links.lock().await;
         links.listen(addr).await
     }
 
+    /// Stop listening on the given address.
+    pub async fn stop_listener(&self, addr: &str) -> Result<(), String> {
+        let mut links = self.links.lock().await;
+        if links.stop_listener(addr) {
+            Ok(())
+        } else {
+            Err("listener not found".to_string())
+        }
+    }
+
+    /// Apply `edit` to the `key` list (`"peers"` or `"listen"`) of the
+    /// config file, and of the running config so that a reload does not
+    /// apply the change a second time. Returns whether the file changed.
+    pub fn persist_list(&self, key: &str, edit: ListEdit) -> Result<bool, String> {
+        // Held while writing, so concurrent calls don't lose each other's edits
+        let path = self.config_path.lock().unwrap();
+        let path = path.as_ref().ok_or("no config file to save to")?;
+        let changed = persist::update_file(path, key, edit)?;
+
+        let mut config = self.config.write().unwrap();
+        let list = match key {
+            "peers" => &mut config.peers,
+            "listen" => &mut config.listen,
+            _ => return Err(format!("unknown list '{}'", key)),
+        };
+        match edit {
+            ListEdit::Add(item) if !list.iter().any(|i| i == item) => list.push(item.to_string()),
+            ListEdit::Add(_) => {}
+            ListEdit::Remove(item) => list.retain(|i| i != item),
+        }
+        Ok(changed)
+    }
+
     /// Add a persistent peer.
     pub async fn add_peer(&self, uri: &str) -> Result<(), String> {
         let mut links = self.links.lock().await;
@@ -232,9 +621,20 @@ impl Core {
         links.remove_peer(uri).await
     }
 
-    /// Get link-level peer info (for admin getPeers).
+    /// Get link-level peer info with the router's port, latency and
+    /// preferred link merged in (for admin getPeers).
     pub async fn get_peers(&self) -> Vec<LinkPeerInfo> {
-        self.active_links.get_peers().await
+        let mut peers = self.active_links.get_peers().await;
+        links::merge_router_peers(&mut peers, &self.inner.get_peers().await);
+        peers
+    }
+
+    /// Keys of the peers we have a link up with, without duplicates.
+    pub async fn peer_keys(&self) -> Vec<[u8; 32]> {
+        let mut keys: Vec<[u8; 32]> = self.get_peers().await.into_iter().filter(|p| p.up).filter_map(|p| p.key).collect();
+        keys.sort();
+        keys.dedup();
+        keys
     }
 
     /// Get spanning tree entries (from ironwood).
@@ -242,6 +642,16 @@ impl Core {
         self.inner.get_tree().await
     }
 
+    /// Get cached paths and pending lookups (for admin getPaths).
+    pub async fn get_paths(&self) -> Vec<ironwood::PathEntry> {
+        self.inner.get_paths().await
+    }
+
+    /// Get end-to-end encrypted sessions (for admin getSessions).
+    pub async fn get_sessions(&self) -> Vec<ironwood::SessionEntry> {
+        self.inner.get_sessions().await
+    }
+
     /// Get the number of routing entries.
     pub async fn routing_entries(&self) -> usize {
         self.inner.routing_entries().await
@@ -251,4 +661,30 @@ impl Core {
     pub async fn tree_coordinates(&self) -> Vec<u64> {
         self.inner.tree_coordinates().await
     }
+
+    /// Get the number of packets ironwood dropped from full queues.
+    pub async fn queue_drops(&self) -> ironwood::QueueDrops {
+        self.inner.queue_drops().await
+    }
+
+    /// Get the number of IPs currently banned after failed handshakes.
+    pub async fn banned_count(&self) -> usize {
+        self.active_links.ban_list.banned_count().await
+    }
+
+    /// Get failed handshakes so far, by reason.
+    pub fn handshake_failures(&self) -> BTreeMap<&'static str, u64> {
+        self.active_links.handshake_failures.counts()
+    }
+}
+
+/// Answer queued remote protocol requests one at a time, until the core is
+/// dropped.
+async fn answer_proto_requests(core: std::sync::Weak<Core>, mut requests: mpsc::Receiver<(Addr, Vec<u8>)>) {
+    while let Some((from, request)) = requests.recv().await {
+        let Some(core) = core.upgrade() else {
+            break;
+        };
+        core.answer_proto(from, &request).await;
+    }
 }
@@ -0,0 +1,333 @@
+//! Network crawler behind the `crawl` admin call.
+//!
+//! Starting from our own node, the mesh is walked breadth-first: every key
+//! we learn about is asked for its peers (remote debug GetPeers) and its
+//! NodeInfo over the session protocol, a limited number of nodes at a time.
+//! The result is an undirected graph that renders as JSON, Graphviz DOT or
+//! GraphML.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use crate::address::addr_for_key;
+use crate::core::Core;
+
+/// Limits for one crawl.
+#[derive(Clone, Debug)]
+pub struct CrawlOptions {
+    /// Nodes queried at the same time.
+    pub concurrency: usize,
+    /// How long to wait for each answer. Requests give up after 6 seconds
+    /// regardless.
+    pub timeout: Duration,
+    /// Stop discovering new nodes once the graph has this many.
+    pub max_nodes: usize,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 16,
+            timeout: Duration::from_secs(5),
+            max_nodes: 10000,
+        }
+    }
+}
+
+/// What a node told us about itself.
+pub struct NodeReport {
+    pub peers: Result<Vec<[u8; 32]>, String>,
+    pub node_info: Option<serde_json::Value>,
+}
+
+/// The crawled network.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub key: String,
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nodeinfo: Option<serde_json::Value>,
+    /// Why the node's peers are unknown, if they are.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Node {
+    fn new(key: &[u8; 32]) -> Self {
+        Self {
+            key: hex::encode(key),
+            address: addr_for_key(key).to_string(),
+            nodeinfo: None,
+            error: None,
+        }
+    }
+
+    /// The NodeInfo `name`, if the node has one.
+    fn name(&self) -> Option<&str> {
+        self.nodeinfo.as_ref()?.get("name")?.as_str()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+}
+
+impl Graph {
+    /// Graphviz DOT. Nodes that did not answer are drawn dashed.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("graph yggdrasil {\n");
+        for node in &self.nodes {
+            let label = match node.name() {
+                Some(name) => format!("{}\\n{}", dot_escape(name), node.address),
+                None => node.address.clone(),
+            };
+            let _ = write!(out, "  \"{}\" [label=\"{}\"", node.key, label);
+            if node.error.is_some() {
+                out.push_str(", style=dashed");
+            }
+            out.push_str("];\n");
+        }
+        for edge in &self.edges {
+            let _ = writeln!(out, "  \"{}\" -- \"{}\";", edge.source, edge.target);
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// GraphML, with the address, name, NodeInfo (as JSON) and error of
+    /// every node as attributes where known.
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"address\" for=\"node\" attr.name=\"address\" attr.type=\"string\"/>\n",
+            "  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n",
+            "  <key id=\"nodeinfo\" for=\"node\" attr.name=\"nodeinfo\" attr.type=\"string\"/>\n",
+            "  <key id=\"error\" for=\"node\" attr.name=\"error\" attr.type=\"string\"/>\n",
+            "  <graph id=\"yggdrasil\" edgedefault=\"undirected\">\n",
+        ));
+        for node in &self.nodes {
+            let _ = writeln!(out, "    <node id=\"{}\">", node.key);
+            let _ = writeln!(out, "      <data key=\"address\">{}</data>", node.address);
+            if let Some(name) = node.name() {
+                let _ = writeln!(out, "      <data key=\"name\">{}</data>", xml_escape(name));
+            }
+            if let Some(info) = &node.nodeinfo {
+                let _ = writeln!(out, "      <data key=\"nodeinfo\">{}</data>", xml_escape(&info.to_string()));
+            }
+            if let Some(error) = &node.error {
+                let _ = writeln!(out, "      <data key=\"error\">{}</data>", xml_escape(error));
+            }
+            out.push_str("    </node>\n");
+        }
+        for edge in &self.edges {
+            let _ = writeln!(out, "    <edge source=\"{}\" target=\"{}\"/>", edge.source, edge.target);
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Crawl the network reachable from `core`.
+pub async fn crawl(core: &Arc<Core>, options: &CrawlOptions) -> Graph {
+    let own = NodeReport {
+        peers: Ok(core.peer_keys().await),
+        node_info: Some(core.node_info()),
+    };
+    let timeout = options.timeout;
+    walk(*core.public_key(), own, options, |key| {
+        let core = core.clone();
+        async move {
+            // One request at a time: while the session to a new node is
+            // being set up, only the latest packet to it is kept
+            let peers = within(timeout, core.debug_remote_get_peers(key)).await;
+            let node_info = match peers {
+                Ok(_) => within(timeout, core.get_node_info(key)).await.ok(),
+                Err(_) => None,
+            };
+            NodeReport { peers, node_info }
+        }
+    })
+    .await
+}
+
+async fn within<T>(timeout: Duration, request: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+    tokio::time::timeout(timeout, request)
+        .await
+        .unwrap_or_else(|_| Err("timed out waiting for response".to_string()))
+}
+
+/// Breadth-first walk from `start`, whose report is already known, asking
+/// `query` about every other node found.
+async fn walk<F, Fut>(start: [u8; 32], report: NodeReport, options: &CrawlOptions, query: F) -> Graph
+where
+    F: Fn([u8; 32]) -> Fut,
+    Fut: Future<Output = NodeReport>,
+{
+    let mut state = Walk {
+        nodes: BTreeMap::new(),
+        edges: BTreeSet::new(),
+        queue: VecDeque::new(),
+        max_nodes: options.max_nodes.max(1),
+    };
+    state.nodes.insert(start, Node::new(&start));
+    state.record(start, report);
+
+    let mut pending = FuturesUnordered::new();
+    loop {
+        while pending.len() < options.concurrency.max(1) {
+            let Some(key) = state.queue.pop_front() else {
+                break;
+            };
+            let request = query(key);
+            pending.push(async move { (key, request.await) });
+        }
+        let Some((key, report)) = pending.next().await else {
+            break;
+        };
+        state.record(key, report);
+    }
+    state.into_graph()
+}
+
+struct Walk {
+    nodes: BTreeMap<[u8; 32], Node>,
+    edges: BTreeSet<([u8; 32], [u8; 32])>,
+    queue: VecDeque<[u8; 32]>,
+    max_nodes: usize,
+}
+
+impl Walk {
+    fn record(&mut self, key: [u8; 32], report: NodeReport) {
+        let (peers, error) = match report.peers {
+            Ok(peers) => (peers, None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        if let Some(node) = self.nodes.get_mut(&key) {
+            node.nodeinfo = report.node_info;
+            node.error = error;
+        }
+        for peer in peers {
+            if peer == key {
+                continue;
+            }
+            if !self.nodes.contains_key(&peer) {
+                if self.nodes.len() >= self.max_nodes {
+                    continue;
+                }
+                self.nodes.insert(peer, Node::new(&peer));
+                self.queue.push_back(peer);
+            }
+            self.edges.insert((key.min(peer), key.max(peer)));
+        }
+    }
+
+    fn into_graph(self) -> Graph {
+        Graph {
+            nodes: self.nodes.into_values().collect(),
+            edges: self
+                .edges
+                .into_iter()
+                .map(|(a, b)| Edge {
+                    source: hex::encode(a),
+                    target: hex::encode(b),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn key(n: u8) -> [u8; 32] {
+        [n; 32]
+    }
+
+    #[tokio::test]
+    async fn test_walk() {
+        // 1 - 2 - 3 - 4, and 2 - 4; node 4 does not answer
+        let topology: HashMap<[u8; 32], Vec<[u8; 32]>> = [
+            (key(2), vec![key(1), key(3), key(4)]),
+            (key(3), vec![key(2), key(4)]),
+        ]
+        .into_iter()
+        .collect();
+        let query = |k: [u8; 32]| {
+            let peers = topology.get(&k).cloned().ok_or_else(|| "timed out waiting for response".to_string());
+            async move {
+                NodeReport {
+                    node_info: peers.as_ref().ok().map(|_| serde_json::json!({ "name": format!("node-{}", k[0]) })),
+                    peers,
+                }
+            }
+        };
+        let own = || NodeReport { peers: Ok(vec![key(2)]), node_info: None };
+
+        let graph = walk(key(1), own(), &CrawlOptions::default(), query).await;
+        assert_eq!(graph.nodes.len(), 4);
+        let edges: Vec<_> = graph.edges.iter().map(|e| (e.source.clone(), e.target.clone())).collect();
+        let pair = |a: u8, b: u8| (hex::encode(key(a)), hex::encode(key(b)));
+        assert_eq!(edges, [pair(1, 2), pair(2, 3), pair(2, 4), pair(3, 4)]);
+        assert_eq!(graph.nodes[2].nodeinfo, Some(serde_json::json!({ "name": "node-3" })));
+        assert!(graph.nodes[3].error.is_some());
+
+        let options = CrawlOptions { max_nodes: 2, ..Default::default() };
+        let graph = walk(key(1), own(), &options, query).await;
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges.len(), 1);
+    }
+
+    #[test]
+    fn test_render() {
+        let mut a = Node::new(&key(1));
+        a.nodeinfo = Some(serde_json::json!({ "name": "say \"hi\" <&>" }));
+        let mut b = Node::new(&key(2));
+        b.error = Some("timed out".to_string());
+        let graph = Graph {
+            edges: vec![Edge { source: a.key.clone(), target: b.key.clone() }],
+            nodes: vec![a, b],
+        };
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("graph yggdrasil {"));
+        assert!(dot.contains("label=\"say \\\"hi\\\" <&>\\n"));
+        assert!(dot.contains(&format!("[label=\"{}\", style=dashed]", graph.nodes[1].address)));
+        assert!(dot.contains(&format!("\"{}\" -- \"{}\";", hex::encode(key(1)), hex::encode(key(2)))));
+
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<data key=\"name\">say &quot;hi&quot; &lt;&amp;&gt;</data>"));
+        assert!(graphml.contains("<data key=\"error\">timed out</data>"));
+        assert!(graphml.contains(&format!("<edge source=\"{}\"", hex::encode(key(1)))));
+
+        let json = serde_json::to_value(&graph).unwrap();
+        assert_eq!(serde_json::from_value::<Graph>(json).unwrap(), graph);
+    }
+}
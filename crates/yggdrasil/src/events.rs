@@ -0,0 +1,208 @@
+//! Node events streamed to admin `subscribe` clients.
+//!
+//! Events are published on a broadcast channel, so emitting one never waits
+//! for a subscriber. A subscriber that falls more than `EVENT_BUFFER`
+//! events behind loses the oldest ones and is told how many it missed.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::address::addr_for_key;
+use crate::links::redact_uri;
+
+/// Events kept for each subscriber before the oldest are dropped.
+const EVENT_BUFFER: usize = 256;
+
+/// Something that happened on the node.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    LinkConnected {
+        uri: String,
+        remote: Option<String>,
+        inbound: bool,
+        #[serde(flatten)]
+        node: NodeRef,
+    },
+    LinkDisconnected {
+        uri: String,
+        remote: Option<String>,
+        inbound: bool,
+        #[serde(flatten)]
+        node: NodeRef,
+        /// Seconds the link was up.
+        uptime: f64,
+        reason: String,
+    },
+    BanAdded {
+        ip: String,
+        reason: String,
+        /// Seconds until the ban is lifted.
+        duration: u64,
+    },
+    /// Our tree root or parent changed; both are public keys.
+    TreeChanged { root: String, parent: String },
+    /// A path to the node was learned (ironwood `path_notify`).
+    PathNotify {
+        #[serde(flatten)]
+        node: NodeRef,
+    },
+    SessionEstablished {
+        #[serde(flatten)]
+        node: NodeRef,
+    },
+    SessionExpired {
+        #[serde(flatten)]
+        node: NodeRef,
+    },
+}
+
+impl Event {
+    /// The `event` tag this event is serialized with.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::LinkConnected { .. } => "link_connected",
+            Event::LinkDisconnected { .. } => "link_disconnected",
+            Event::BanAdded { .. } => "ban_added",
+            Event::TreeChanged { .. } => "tree_changed",
+            Event::PathNotify { .. } => "path_notify",
+            Event::SessionEstablished { .. } => "session_established",
+            Event::SessionExpired { .. } => "session_expired",
+        }
+    }
+
+    /// Leave secrets out of link URIs, for clients with read access only.
+    pub fn redact(&mut self) {
+        if let Event::LinkConnected { uri, .. } | Event::LinkDisconnected { uri, .. } = self {
+            *uri = redact_uri(uri);
+        }
+    }
+}
+
+/// Names of all events, for validating subscription filters.
+pub const EVENT_NAMES: &[&str] = &[
+    "link_connected",
+    "link_disconnected",
+    "ban_added",
+    "tree_changed",
+    "path_notify",
+    "session_established",
+    "session_expired",
+];
+
+/// A remote node by key and address.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NodeRef {
+    pub key: String,
+    pub address: String,
+}
+
+impl NodeRef {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            key: hex::encode(key),
+            address: addr_for_key(key).to_string(),
+        }
+    }
+}
+
+/// An event with the time it happened, in seconds since the Unix epoch.
+#[derive(Clone, Debug, Serialize)]
+pub struct EventRecord {
+    pub time: f64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// The node's event channel.
+#[derive(Clone)]
+pub struct Events(broadcast::Sender<EventRecord>);
+
+impl Events {
+    pub fn new() -> Self {
+        Self(broadcast::channel(EVENT_BUFFER).0)
+    }
+
+    /// Publish an event. Without subscribers it is simply dropped.
+    pub fn emit(&self, event: Event) {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        let _ = self.0.send(EventRecord { time, event });
+    }
+
+    /// Receive every event emitted from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<EventRecord> {
+        self.0.subscribe()
+    }
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_events() {
+        let events = Events::new();
+        // Nobody listening yet
+        events.emit(Event::TreeChanged { root: String::new(), parent: String::new() });
+
+        let mut rx = events.subscribe();
+        events.emit(Event::SessionEstablished { node: NodeRef::new(&[1; 32]) });
+        let record = rx.recv().await.unwrap();
+        assert!(record.time > 0.0);
+        assert_eq!(record.event.name(), "session_established");
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["event"], "session_established");
+        assert_eq!(json["key"], hex::encode([1u8; 32]));
+        assert_eq!(json["address"], addr_for_key(&[1; 32]).to_string());
+        assert!(json["time"].is_f64());
+
+        for _ in 0..EVENT_BUFFER + 2 {
+            events.emit(Event::PathNotify { node: NodeRef::new(&[2; 32]) });
+        }
+        assert!(matches!(rx.recv().await, Err(broadcast::error::RecvError::Lagged(2))));
+        assert_eq!(rx.recv().await.unwrap().event.name(), "path_notify");
+    }
+
+    #[test]
+    fn test_event_names() {
+        let node = || NodeRef::new(&[3; 32]);
+        let all = [
+            Event::LinkConnected { uri: String::new(), remote: None, inbound: false, node: node() },
+            Event::LinkDisconnected { uri: String::new(), remote: None, inbound: false, node: node(), uptime: 0.0, reason: String::new() },
+            Event::BanAdded { ip: String::new(), reason: String::new(), duration: 0 },
+            Event::TreeChanged { root: String::new(), parent: String::new() },
+            Event::PathNotify { node: node() },
+            Event::SessionEstablished { node: node() },
+            Event::SessionExpired { node: node() },
+        ];
+        for (event, name) in all.iter().zip(EVENT_NAMES) {
+            assert_eq!(event.name(), *name);
+            assert_eq!(serde_json::to_value(event).unwrap()["event"], *name);
+        }
+        assert_eq!(all.len(), EVENT_NAMES.len());
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut event = Event::LinkConnected {
+            uri: "tls://192.0.2.1:443?password=secret".to_string(),
+            remote: None,
+            inbound: false,
+            node: NodeRef::new(&[4; 32]),
+        };
+        event.redact();
+        assert_eq!(serde_json::to_value(&event).unwrap()["uri"], "tls://192.0.2.1:443");
+    }
+}
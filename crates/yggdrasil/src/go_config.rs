@@ -0,0 +1,267 @@
+//! Importing yggdrasil-go configs.
+//!
+//! yggdrasil-go writes its config as HJSON (or JSON) with `PascalCase`
+//! keys. The settings that have an equivalent here are converted; the rest
+//! are reported as warnings, so nothing is dropped without a word. Keys are
+//! matched without regard to case, as yggdrasil-go does.
+
+use serde_json::Value;
+
+use crate::config::{Config, MulticastInterfaceConfig};
+use crate::hjson;
+
+/// Convert a yggdrasil-go config into a `Config`, with a warning for every
+/// setting that was left out. Settings missing from the file get the same
+/// defaults as in a TOML config.
+pub fn import(text: &str) -> Result<(Config, Vec<String>), String> {
+    let value = hjson::parse(text)?;
+    let mut config: Config = toml::from_str("").map_err(|e| e.to_string())?;
+    let mut warnings = Vec::new();
+    let mut interface_peers = Vec::new();
+
+    for (key, value) in value.as_object().into_iter().flatten() {
+        match key.to_lowercase().as_str() {
+            "privatekey" => config.private_key = string(key, value)?,
+            "peers" => config.peers = strings(key, value)?,
+            "interfacepeers" => {
+                let interfaces = value.as_object().ok_or_else(|| format!("{}: expected an object", key))?;
+                for (interface, peers) in interfaces {
+                    for uri in strings(&format!("{}.{}", key, interface), peers)? {
+                        interface_peers.push(with_source_interface(&uri, interface));
+                    }
+                }
+            }
+            "listen" => config.listen = strings(key, value)?,
+            "adminlisten" => {
+                config.admin_listen = match string(key, value)?.as_str() {
+                    "none" => String::new(),
+                    listen => listen.to_string(),
+                }
+            }
+            "multicastinterfaces" => config.multicast_interfaces = multicast_interfaces(key, value, &mut warnings)?,
+            "allowedpublickeys" => config.allowed_public_keys = strings(key, value)?,
+            "ifname" => config.if_name = string(key, value)?,
+            "ifmtu" => config.if_mtu = value.as_u64().ok_or_else(|| format!("{}: expected a number", key))?,
+            "nodeinfoprivacy" => config.node_info_privacy = boolean(key, value)?,
+            "nodeinfo" if value.is_null() => {}
+            "nodeinfo" => {
+                if !value.is_object() {
+                    return Err(format!("{}: expected an object", key));
+                }
+                config.node_info = toml_value(key, value, &mut warnings).unwrap_or(config.node_info);
+            }
+            _ => warnings.push(format!("{}: not supported, ignored", key)),
+        }
+    }
+    config.peers.extend(interface_peers);
+    Ok((config, warnings))
+}
+
+fn string(key: &str, value: &Value) -> Result<String, String> {
+    value
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("{}: expected a string", key))
+}
+
+fn strings(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    if value.is_null() {
+        return Ok(Vec::new());
+    }
+    value
+        .as_array()
+        .and_then(|items| items.iter().map(|v| v.as_str().map(str::to_string)).collect())
+        .ok_or_else(|| format!("{}: expected a list of strings", key))
+}
+
+fn boolean(key: &str, value: &Value) -> Result<bool, String> {
+    value.as_bool().ok_or_else(|| format!("{}: expected true or false", key))
+}
+
+/// An `InterfacePeers` entry: the peer, dialed from `interface`.
+fn with_source_interface(uri: &str, interface: &str) -> String {
+    let separator = if uri.contains('?') { '&' } else { '?' };
+    format!("{}{}sintf={}", uri, separator, interface)
+}
+
+/// Entries are objects, or just a regex in configs from before 0.4.
+/// Fields left out are off, as in yggdrasil-go.
+fn multicast_interfaces(key: &str, value: &Value, warnings: &mut Vec<String>) -> Result<Vec<MulticastInterfaceConfig>, String> {
+    let entries = match value {
+        Value::Null => return Ok(Vec::new()),
+        Value::Array(entries) => entries,
+        _ => return Err(format!("{}: expected a list", key)),
+    };
+    let mut interfaces = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let key = format!("{}[{}]", key, i);
+        let mut interface = MulticastInterfaceConfig {
+            regex: String::new(),
+            beacon: false,
+            listen: false,
+            port: 0,
+            priority: 0,
+            password: String::new(),
+        };
+        match entry {
+            Value::String(regex) => {
+                interface.regex = regex.clone();
+                interface.beacon = true;
+                interface.listen = true;
+            }
+            Value::Object(fields) => {
+                for (field, value) in fields {
+                    let name = format!("{}.{}", key, field);
+                    match field.to_lowercase().as_str() {
+                        "regex" => interface.regex = string(&name, value)?,
+                        "beacon" => interface.beacon = boolean(&name, value)?,
+                        "listen" => interface.listen = boolean(&name, value)?,
+                        "port" => {
+                            interface.port = value
+                                .as_u64()
+                                .and_then(|n| u16::try_from(n).ok())
+                                .ok_or_else(|| format!("{}: expected a port number", name))?
+                        }
+                        "priority" => {
+                            interface.priority = value
+                                .as_u64()
+                                .and_then(|n| u8::try_from(n).ok())
+                                .ok_or_else(|| format!("{}: expected a number from 0 to 255", name))?
+                        }
+                        "password" => interface.password = string(&name, value)?,
+                        _ => warnings.push(format!("{}: not supported, ignored", name)),
+                    }
+                }
+            }
+            _ => return Err(format!("{}: expected an object", key)),
+        }
+        interfaces.push(interface);
+    }
+    Ok(interfaces)
+}
+
+/// The TOML equivalent of a JSON value. TOML has no null, so nulls are
+/// left out with a warning.
+fn toml_value(key: &str, value: &Value, warnings: &mut Vec<String>) -> Option<toml::Value> {
+    Some(match value {
+        Value::Null => {
+            warnings.push(format!("{}: null can't be written in TOML, left out", key));
+            return None;
+        }
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None => toml::Value::Float(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Array(items) => toml::Value::Array(
+            items
+                .iter()
+                .enumerate()
+                .filter_map(|(i, item)| toml_value(&format!("{}[{}]", key, i), item, warnings))
+                .collect(),
+        ),
+        Value::Object(fields) => toml::Value::Table(
+            fields
+                .iter()
+                .filter_map(|(field, item)| Some((field.clone(), toml_value(&format!("{}.{}", key, field), item, warnings)?)))
+                .collect(),
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // As written by yggdrasil-go 0.5 with -genconf, shortened
+    const GO_CONFIG: &str = r#"{
+  # Your private key. DO NOT share this with anyone!
+  PrivateKey: 5d7d9f1de7a1b4b1b4e4a1a0e5e5a1b4b1b4e4a1a0e5e5a1b4b1b4e4a1a0e5e55d7d9f1de7a1b4b1b4e4a1a0e5e5a1b4b1b4e4a1a0e5e5a1b4b1b4e4a1a0e5e5
+
+  Peers: [
+    tls://192.0.2.1:443
+  ]
+  InterfacePeers: {
+    eth0: [
+      tcp://[fe80::1%eth0]:9001
+      tls://[fe80::2%eth0]:443?key=00
+    ]
+  }
+  Listen: [
+    tls://[::]:0
+  ]
+  AdminListen: none
+  MulticastInterfaces: [
+    {
+      Regex: .*
+      Beacon: true
+      Listen: true
+      Port: 0
+      Priority: 0
+      Password: ""
+    }
+  ]
+  AllowedPublicKeys: []
+  IfName: auto
+  IfMTU: 65535
+  LogLookups: false
+  NodeInfoPrivacy: true
+  NodeInfo: {
+    name: my-node
+    location: { lat: 52.5, lon: 13.4 }
+    contact: null
+  }
+}
+"#;
+
+    #[test]
+    fn test_import() {
+        let (config, warnings) = import(GO_CONFIG).unwrap();
+        assert_eq!(config.private_key.len(), 128);
+        assert_eq!(
+            config.peers,
+            [
+                "tls://192.0.2.1:443",
+                "tcp://[fe80::1%eth0]:9001?sintf=eth0",
+                "tls://[fe80::2%eth0]:443?key=00&sintf=eth0",
+            ]
+        );
+        assert_eq!(config.listen, ["tls://[::]:0"]);
+        assert_eq!(config.admin_listen, "");
+        assert_eq!(config.multicast_interfaces.len(), 1);
+        assert_eq!(config.multicast_interfaces[0].regex, ".*");
+        assert!(config.multicast_interfaces[0].beacon);
+        assert_eq!(config.if_name, "auto");
+        assert_eq!(config.if_mtu, 65535);
+        assert!(config.node_info_privacy);
+        assert_eq!(config.node_info["name"].as_str(), Some("my-node"));
+        assert_eq!(config.node_info["location"]["lat"].as_float(), Some(52.5));
+        assert!(config.node_info.get("contact").is_none());
+        assert_eq!(
+            warnings,
+            ["LogLookups: not supported, ignored", "NodeInfo.contact: null can't be written in TOML, left out"]
+        );
+
+        // The converted config reads back the same from TOML
+        let text = toml::to_string(&config).unwrap();
+        let again: Config = toml::from_str(&text).unwrap();
+        assert_eq!(again.peers, config.peers);
+        assert_eq!(again.multicast_interfaces, config.multicast_interfaces);
+        assert_eq!(again.node_info, config.node_info);
+    }
+
+    #[test]
+    fn test_import_json() {
+        let json = r#"{"peers": ["tcp://192.0.2.1:9001"], "MulticastInterfaces": [".*"], "IfMTU": 1280, "SessionFirewall": {}}"#;
+        let (config, warnings) = import(json).unwrap();
+        assert_eq!(config.peers, ["tcp://192.0.2.1:9001"]);
+        assert!(config.multicast_interfaces[0].beacon && config.multicast_interfaces[0].listen);
+        assert_eq!(config.if_mtu, 1280);
+        assert_eq!(warnings, ["SessionFirewall: not supported, ignored"]);
+
+        assert_eq!(import("{ Peers: \"tcp://192.0.2.1:9001\" }").unwrap_err(), "Peers: expected a list of strings");
+        assert_eq!(import("{ IfMTU: \"big\" }").unwrap_err(), "IfMTU: expected a number");
+        assert!(import("{ MulticastInterfaces: [{ Port: 70000 }] }").unwrap_err().starts_with("MulticastInterfaces[0].Port"));
+    }
+}
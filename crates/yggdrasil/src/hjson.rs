@@ -0,0 +1,347 @@
+//! A reader for HJSON, the format yggdrasil-go writes its config in.
+//!
+//! HJSON is JSON with comments (`#`, `//`, `/* */`), optional commas and
+//! braces around the top-level object, unquoted keys, and strings that run
+//! to the end of the line without quotes. Plain JSON is valid HJSON. Only
+//! reading is needed, into a `serde_json::Value`; errors give the line and
+//! column.
+
+use serde_json::{Map, Number, Value};
+
+/// Parse an HJSON document whose top level is an object.
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser { text, pos: 0 };
+    parser.skip_space()?;
+    let value = if parser.peek() == Some('{') {
+        parser.value()?
+    } else {
+        // Braces around the top level may be left out
+        parser.members(None)?
+    };
+    parser.skip_space()?;
+    if parser.pos < text.len() {
+        return Err(parser.error("unexpected text after the top-level object"));
+    }
+    if !value.is_object() {
+        return Err("the config must be an object".to_string());
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        let before = &self.text[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+        format!("line {}, column {}: {}", line, column, message)
+    }
+
+    /// Skip whitespace, newlines and comments.
+    fn skip_space(&mut self) -> Result<(), String> {
+        loop {
+            let rest = self.rest();
+            if rest.starts_with('#') || rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if let Some(comment) = rest.strip_prefix("/*") {
+                let end = comment.find("*/").ok_or_else(|| self.error("unterminated comment"))?;
+                self.pos += end + 4;
+            } else if self.peek().is_some_and(char::is_whitespace) {
+                self.bump();
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Whether a value ends here: only spaces before a line end, a comma,
+    /// a closing bracket, a comment or the end of the text.
+    fn at_value_end(&self) -> bool {
+        let rest = self.rest().trim_start_matches([' ', '\t', '\r']);
+        rest.is_empty()
+            || rest.starts_with(['\n', ',', ']', '}', '#'])
+            || rest.starts_with("//")
+            || rest.starts_with("/*")
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                self.members(Some('}'))
+            }
+            Some('[') => {
+                self.bump();
+                self.elements()
+            }
+            Some('"') => self.quoted('"').map(Value::String),
+            Some('\'') if self.rest().starts_with("'''") => self.multiline().map(Value::String),
+            Some('\'') => self.quoted('\'').map(Value::String),
+            Some(c) if "]},:".contains(c) => Err(self.error(&format!("expected a value, found '{}'", c))),
+            Some(_) => Ok(self.quoteless()),
+            None => Err(self.error("expected a value, found the end of the text")),
+        }
+    }
+
+    /// Object members up to `close`, or to the end of the text without one.
+    fn members(&mut self, close: Option<char>) -> Result<Value, String> {
+        let mut object = Map::new();
+        loop {
+            self.skip_space()?;
+            match (self.peek(), close) {
+                (Some(c), Some(close)) if c == close => {
+                    self.bump();
+                    return Ok(Value::Object(object));
+                }
+                (None, None) => return Ok(Value::Object(object)),
+                (None, Some(close)) => return Err(self.error(&format!("missing '{}'", close))),
+                _ => {}
+            }
+            let key = self.key()?;
+            self.skip_space()?;
+            if self.bump() != Some(':') {
+                return Err(self.error(&format!("expected ':' after '{}'", key)));
+            }
+            self.skip_space()?;
+            let value = self.value()?;
+            object.insert(key, value);
+            self.separator()?;
+        }
+    }
+
+    fn elements(&mut self) -> Result<Value, String> {
+        let mut array = Vec::new();
+        loop {
+            self.skip_space()?;
+            match self.peek() {
+                Some(']') => {
+                    self.bump();
+                    return Ok(Value::Array(array));
+                }
+                None => return Err(self.error("missing ']'")),
+                _ => {}
+            }
+            array.push(self.value()?);
+            self.separator()?;
+        }
+    }
+
+    /// Skip the optional comma after a member or element.
+    fn separator(&mut self) -> Result<(), String> {
+        self.skip_space()?;
+        if self.peek() == Some(',') {
+            self.bump();
+        }
+        Ok(())
+    }
+
+    fn key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(q @ ('"' | '\'')) => self.quoted(q),
+            _ => {
+                let len = self
+                    .rest()
+                    .find(|c: char| c == ':' || c.is_whitespace() || ",{}[]".contains(c))
+                    .unwrap_or(self.rest().len());
+                if len == 0 {
+                    return Err(self.error("expected a key"));
+                }
+                let key = self.rest()[..len].to_string();
+                self.pos += len;
+                Ok(key)
+            }
+        }
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String, String> {
+        let start = self.pos;
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => return Ok(s),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => self.unicode_escape()?,
+                        Some(c @ ('"' | '\'' | '\\' | '/')) => c,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    s.push(c);
+                }
+                Some('\n') | None => {
+                    self.pos = start;
+                    return Err(self.error("unterminated string"));
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) && self.rest().starts_with("\\u") {
+            self.pos += 2;
+            let low = self.hex4()?;
+            0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.rest().get(..4).ok_or_else(|| self.error("invalid unicode escape"))?;
+        let n = u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(n)
+    }
+
+    /// A `'''` string. Its first line is skipped if empty, and the
+    /// indentation of the opening quotes is removed from every line.
+    fn multiline(&mut self) -> Result<String, String> {
+        let indent = self.text[..self.pos].rsplit('\n').next().unwrap_or_default().chars().count();
+        let start = self.pos;
+        self.pos += 3;
+        let end = self.rest().find("'''").ok_or_else(|| {
+            self.pos = start;
+            self.error("unterminated string")
+        })?;
+        let raw = &self.rest()[..end];
+        self.pos += end + 3;
+
+        let raw = match raw.find('\n') {
+            Some(n) if raw[..n].trim().is_empty() => &raw[n + 1..],
+            _ => raw,
+        };
+        let lines: Vec<String> = raw
+            .split('\n')
+            .map(|line| {
+                let skip = line.chars().take(indent).take_while(|c| c.is_whitespace()).count();
+                line.chars().skip(skip).collect()
+            })
+            .collect();
+        let s = lines.join("\n");
+        Ok(s.strip_suffix('\n').unwrap_or(&s).trim_end_matches('\r').to_string())
+    }
+
+    /// `true`, `false`, `null` and numbers when nothing else follows on the
+    /// line, otherwise a string up to the end of the line.
+    fn quoteless(&mut self) -> Value {
+        let rest = self.rest();
+        let token_len = rest
+            .find(|c: char| c.is_whitespace() || ",]}#/".contains(c))
+            .unwrap_or(rest.len());
+        let token = &rest[..token_len];
+        let literal = match token {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            "null" => Some(Value::Null),
+            _ => serde_json::from_str::<Number>(token).ok().map(Value::Number),
+        };
+        if let Some(literal) = literal {
+            let start = self.pos;
+            self.pos += token_len;
+            if self.at_value_end() {
+                return literal;
+            }
+            self.pos = start;
+        }
+        let line_len = rest.find('\n').unwrap_or(rest.len());
+        self.pos += line_len;
+        Value::String(rest[..line_len].trim_end().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+{
+  # Your private key
+  PrivateKey: abc123
+  Peers: [
+    tls://192.0.2.1:443
+    "tcp://192.0.2.2:9001" // quoted
+  ]
+  /* Interfaces
+     to peer on */
+  InterfacePeers: { eth0: ["tcp://[fe80::1%eth0]:9001"] }
+  AdminListen: unix:///var/run/yggdrasil.sock
+  MulticastInterfaces: [
+    {
+      Regex: .*
+      Beacon: true
+      Port: 0
+      Password: ""
+    }
+  ]
+  IfMTU: 65535, NodeInfoPrivacy: false
+  'quoted key': 'it\'s'
+  Escapes: "a\"bé\n"
+  NodeInfo: {
+    name: my node # still the name
+    ratio: 1.5
+    notes:
+      '''
+      first
+        second
+      '''
+  }
+}
+"#;
+        let value = parse(text).unwrap();
+        assert_eq!(value["PrivateKey"], "abc123");
+        assert_eq!(value["Peers"], json!(["tls://192.0.2.1:443", "tcp://192.0.2.2:9001"]));
+        assert_eq!(value["InterfacePeers"], json!({ "eth0": ["tcp://[fe80::1%eth0]:9001"] }));
+        assert_eq!(value["AdminListen"], "unix:///var/run/yggdrasil.sock");
+        assert_eq!(value["MulticastInterfaces"], json!([{ "Regex": ".*", "Beacon": true, "Port": 0, "Password": "" }]));
+        assert_eq!(value["IfMTU"], 65535);
+        assert_eq!(value["NodeInfoPrivacy"], false);
+        assert_eq!(value["quoted key"], "it's");
+        assert_eq!(value["Escapes"], "a\"b\u{e9}\n");
+        assert_eq!(value["NodeInfo"]["name"], "my node # still the name");
+        assert_eq!(value["NodeInfo"]["ratio"], 1.5);
+        assert_eq!(value["NodeInfo"]["notes"], "first\n  second");
+    }
+
+    #[test]
+    fn test_json_and_braceless() {
+        let json = r#"{"Peers": ["tcp://192.0.2.1:9001"], "IfMTU": 1280, "NodeInfo": {"a": null}}"#;
+        assert_eq!(parse(json).unwrap(), serde_json::from_str::<Value>(json).unwrap());
+
+        let value = parse("IfName: auto\nIfMTU: 1280\n").unwrap();
+        assert_eq!(value, json!({ "IfName": "auto", "IfMTU": 1280 }));
+
+        assert!(parse("{ Peers: [").unwrap_err().contains("missing ']'"));
+        assert!(parse("{ IfName: \"auto }").unwrap_err().starts_with("line 1, column 11: unterminated string"));
+        assert!(parse("[1, 2]").is_err());
+        assert!(parse("{} x").is_err());
+    }
+}
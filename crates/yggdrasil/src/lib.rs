@@ -1,8 +1,17 @@
 pub mod address;
 pub mod admin;
+pub mod check;
 pub mod config;
 pub mod core;
+pub mod crawl;
+pub mod events;
+pub mod go_config;
+pub mod hjson;
 pub mod ipv6rwc;
 pub mod links;
+pub mod metrics;
+pub mod multicast;
+pub mod persist;
+pub mod proto;
 pub mod tun;
 pub mod version;
@@ -0,0 +1,189 @@
+//! Source binding for outbound links (`sintf=`) and link-local scoped peers.
+//!
+//! `sintf=eth1` pins a link to an interface (`SO_BINDTODEVICE` on Linux,
+//! elsewhere one of the interface's addresses is used as the source) and
+//! `sintf=192.0.2.1` binds it to a source address. Link-local peers carry
+//! their zone in the host, as in `tcp://[fe80::1%eth0]:9001`; the
+//! yggdrasil-go spelling `%25eth0` is accepted as well.
+
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
+
+use percent_encoding::percent_decode_str;
+use socket2::SockRef;
+use tokio::net::{TcpSocket, TcpStream};
+
+/// Where outbound sockets of a link are bound, from `sintf=`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Interface(String),
+    Address(IpAddr),
+}
+
+impl Source {
+    pub(crate) fn parse(value: &str) -> Result<Self, String> {
+        if value.is_empty() {
+            return Err("empty sintf".to_string());
+        }
+        Ok(match value.parse::<IpAddr>() {
+            Ok(ip) => Source::Address(ip),
+            Err(_) => Source::Interface(value.to_string()),
+        })
+    }
+
+    /// Bind a not yet connected socket for a connection to `remote`.
+    pub(crate) fn bind(&self, socket: &SockRef<'_>, remote: &SocketAddr) -> io::Result<()> {
+        match self {
+            Source::Address(ip) => {
+                if ip.is_ipv4() != remote.is_ipv4() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("source {} and peer {} are of different address families", ip, remote),
+                    ));
+                }
+                socket.bind(&with_scope(*ip, remote).into())
+            }
+            Source::Interface(name) => bind_interface(socket, name, remote),
+        }
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "fuchsia"))]
+fn bind_interface(socket: &SockRef<'_>, name: &str, _remote: &SocketAddr) -> io::Result<()> {
+    socket.bind_device(Some(name.as_bytes()))
+}
+
+/// Without `SO_BINDTODEVICE`, bind to an address of the interface instead.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "fuchsia")))]
+fn bind_interface(socket: &SockRef<'_>, name: &str, remote: &SocketAddr) -> io::Result<()> {
+    let wants_link_local = matches!(remote.ip(), IpAddr::V6(ip) if ip.is_unicast_link_local());
+    let ip = getifaddrs::getifaddrs()?
+        .filter(|i| i.name == name)
+        .filter_map(|i| i.address.ip_addr())
+        .find(|ip| match ip {
+            IpAddr::V4(_) => remote.is_ipv4(),
+            IpAddr::V6(ip) => remote.is_ipv6() && ip.is_unicast_link_local() == wants_link_local,
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                format!("interface {} has no address usable for {}", name, remote),
+            )
+        })?;
+    socket.bind(&with_scope(ip, remote).into())
+}
+
+/// Source address for `ip`, carrying over the peer's scope ID.
+fn with_scope(ip: IpAddr, remote: &SocketAddr) -> SocketAddr {
+    match (ip, remote) {
+        (IpAddr::V6(ip), SocketAddr::V6(remote)) => SocketAddrV6::new(ip, 0, 0, remote.scope_id()).into(),
+        _ => SocketAddr::new(ip, 0),
+    }
+}
+
+/// Take the IPv6 zone out of a URI's host, since `Url` cannot parse it:
+/// `tcp://[fe80::1%eth0]:9001` gives `tcp://[fe80::1]:9001` and `eth0`.
+pub(crate) fn split_zone(uri: &str) -> (String, Option<String>) {
+    let Some(start) = uri.find("://").map(|i| i + 3) else {
+        return (uri.to_string(), None);
+    };
+    let end = uri[start..].find(['/', '?', '#']).map_or(uri.len(), |i| start + i);
+    let authority = &uri[start..end];
+    let host_start = start + authority.rfind('@').map_or(0, |i| i + 1);
+    let Some(close) = uri[host_start..end].find(']').map(|i| host_start + i) else {
+        return (uri.to_string(), None);
+    };
+    let Some(pct) = uri[host_start..close].find('%').map(|i| host_start + i) else {
+        return (uri.to_string(), None);
+    };
+    // "%25eth0" decodes to "%eth0"; a bare "%eth0" is left as it is
+    let zone = percent_decode_str(&uri[pct..close]).decode_utf8_lossy();
+    let zone = zone.trim_start_matches('%').to_string();
+    (format!("{}{}", &uri[..pct], &uri[close..]), Some(zone))
+}
+
+/// Interface index for a zone, given by name or number.
+pub(crate) fn zone_index(zone: &str) -> Result<u32, String> {
+    if let Ok(index) = zone.parse() {
+        return Ok(index);
+    }
+    getifaddrs::if_nametoindex(zone).map_err(|e| format!("unknown interface {}: {}", zone, e))
+}
+
+/// Resolve a "host:port" target. A `[addr%zone]:port` target gives the
+/// zone's interface as scope ID instead of going through DNS.
+pub(crate) async fn resolve(target: &str) -> Result<Vec<SocketAddr>, String> {
+    if let Some((host, port)) = target.strip_prefix('[').and_then(|t| t.split_once("]:")) {
+        if let Some((ip, zone)) = host.split_once('%') {
+            let ip: Ipv6Addr = ip.parse().map_err(|e| format!("invalid address {}: {}", ip, e))?;
+            let port = port.parse().map_err(|_| format!("invalid port: {}", port))?;
+            return Ok(vec![SocketAddrV6::new(ip, port, 0, zone_index(zone)?).into()]);
+        }
+    }
+    let addrs: Vec<_> = tokio::net::lookup_host(target)
+        .await
+        .map_err(|e| format!("DNS lookup failed for {}: {}", target, e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("no address resolved for {}", target));
+    }
+    Ok(addrs)
+}
+
+/// Connect to `addr` with the socket bound to `source`, if given.
+pub(crate) async fn connect_tcp(addr: SocketAddr, source: Option<&Source>) -> io::Result<TcpStream> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    if let Some(source) = source {
+        source.bind(&SockRef::from(&socket), &addr)?;
+    }
+    let stream = socket.connect(addr).await?;
+    stream.set_nodelay(true).ok();
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_split_zone() {
+        assert_eq!(
+            split_zone("tcp://[fe80::1%eth0]:9001?key=00"),
+            ("tcp://[fe80::1]:9001?key=00".to_string(), Some("eth0".to_string()))
+        );
+        assert_eq!(
+            split_zone("tls://[fe80::1%25wlan0]:443"),
+            ("tls://[fe80::1]:443".to_string(), Some("wlan0".to_string()))
+        );
+        assert_eq!(split_zone("tcp://[::1]:9001"), ("tcp://[::1]:9001".to_string(), None));
+        // Only the host is looked at
+        let uri = "socks://proxy/[fe80::1%eth0]:9001";
+        assert_eq!(split_zone(uri), (uri.to_string(), None));
+        assert!(url::Url::parse(&split_zone("tcp://[fe80::1%eth0]:9001").0).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_zone() {
+        let addrs = resolve("[fe80::1%7]:9001").await.unwrap();
+        assert_eq!(addrs, [SocketAddr::from(SocketAddrV6::new("fe80::1".parse().unwrap(), 9001, 0, 7))]);
+        assert!(resolve("[fe80::1%no-such-interface0]:9001").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_from_source_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap();
+        let source = Source::parse("127.0.0.1").unwrap();
+        let stream = connect_tcp(target, Some(&source)).await.unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip(), IpAddr::from([127, 0, 0, 1]));
+
+        // An IPv6 source cannot reach an IPv4 peer
+        let source = Source::parse("::1").unwrap();
+        assert!(connect_tcp(target, Some(&source)).await.is_err());
+    }
+}
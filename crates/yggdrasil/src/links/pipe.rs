@@ -0,0 +1,147 @@
+//! Process pipe transport (`exec:`, `pipe:`), outbound only.
+//!
+//! Spawns a command and uses its stdin/stdout as the link, like an OpenSSH
+//! `ProxyCommand`: `exec:ssh?arg=jumphost&arg=nc&arg=::1&arg=12345`. The URI
+//! path is the program (looked up in `PATH` unless absolute) and each `arg=`
+//! query parameter is one argument, in order. The child's stderr is logged.
+
+use std::pin::Pin;
+use std::process::Stdio;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader, ReadBuf};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+/// Command line taken from an `exec:` or `pipe:` URI.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PipeCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl PipeCommand {
+    pub(crate) fn parse(url: &url::Url) -> Result<Self, String> {
+        if url.host_str().is_some_and(|h| !h.is_empty()) {
+            return Err(format!(
+                "{} URI must not have a host, use {}:program or {}:///path/to/program",
+                url.scheme(),
+                url.scheme(),
+                url.scheme()
+            ));
+        }
+        let program = percent_encoding::percent_decode_str(url.path())
+            .decode_utf8()
+            .map_err(|_| "invalid program path".to_string())?
+            .into_owned();
+        if program.is_empty() {
+            return Err("missing program".to_string());
+        }
+        let args = url
+            .query_pairs()
+            .filter(|(k, _)| k == "arg")
+            .map(|(_, v)| v.into_owned())
+            .collect();
+        Ok(Self { program, args })
+    }
+
+    /// Start the command with piped stdio.
+    pub(crate) fn spawn(&self) -> Result<ChildStream, String> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("spawn {}: {}", self.program, e))?;
+
+        let stdin = child.stdin.take().ok_or("child has no stdin")?;
+        let stdout = child.stdout.take().ok_or("child has no stdout")?;
+        if let Some(stderr) = child.stderr.take() {
+            let program = self.program.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!("{}: {}", program, line);
+                }
+            });
+        }
+
+        Ok(ChildStream {
+            stdin,
+            stdout,
+            _child: child,
+        })
+    }
+}
+
+impl std::fmt::Display for PipeCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// A child's stdout and stdin as one stream.
+/// The child is killed when the stream is dropped.
+pub(crate) struct ChildStream {
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    _child: Child,
+}
+
+impl AsyncRead for ChildStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stdout).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ChildStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.stdin).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stdin).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.stdin).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_parse() {
+        let url = url::Url::parse("exec:ssh?arg=jump&arg=nc&arg=::1&arg=12345&key=00").unwrap();
+        let cmd = PipeCommand::parse(&url).unwrap();
+        assert_eq!(cmd.program, "ssh");
+        assert_eq!(cmd.args, ["jump", "nc", "::1", "12345"]);
+        assert_eq!(cmd.to_string(), "ssh jump nc ::1 12345");
+
+        let url = url::Url::parse("pipe:///usr/bin/my%20tunnel").unwrap();
+        assert_eq!(PipeCommand::parse(&url).unwrap().program, "/usr/bin/my tunnel");
+
+        let url = url::Url::parse("exec://host/bin/sh").unwrap();
+        assert!(PipeCommand::parse(&url).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_child_stdio_roundtrip() {
+        let url = url::Url::parse("exec:cat").unwrap();
+        let mut stream = PipeCommand::parse(&url).unwrap().spawn().unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        stream.flush().await.unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+}
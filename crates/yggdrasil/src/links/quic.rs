@@ -0,0 +1,200 @@
+//! QUIC transport (`quic://`).
+//!
+//! Each link is one QUIC connection carrying a single bidirectional stream.
+//! The connection is secured with the same self-signed node certificate as
+//! `tls://`, so `?key=` pins and `sni=` work the same way.
+
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::{Connection, Endpoint, EndpointConfig, Incoming, RecvStream, SendStream, TokioRuntime, TransportConfig};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::tls::TlsIdentity;
+use super::bind::Source;
+use super::LinkConn;
+
+/// Idle timeout for QUIC connections (same as yggdrasil-go).
+const MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Keepalive interval, well below the idle timeout.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(20);
+
+/// One QUIC bidirectional stream used as a link.
+/// Holds the connection (and the client endpoint) so they live as long as the link.
+pub(crate) struct QuicStream {
+    send: SendStream,
+    recv: RecvStream,
+    _conn: Connection,
+    _endpoint: Option<Endpoint>,
+}
+
+impl AsyncRead for QuicStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.recv).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for QuicStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        AsyncWrite::poll_write(Pin::new(&mut self.send), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        AsyncWrite::poll_flush(Pin::new(&mut self.send), cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        AsyncWrite::poll_shutdown(Pin::new(&mut self.send), cx)
+    }
+}
+
+fn transport_config() -> Arc<TransportConfig> {
+    let mut transport = TransportConfig::default();
+    transport
+        .max_idle_timeout(MAX_IDLE_TIMEOUT.try_into().ok())
+        .keep_alive_interval(Some(KEEP_ALIVE_INTERVAL))
+        // One stream per link, always opened by the dialing side.
+        .max_concurrent_bidi_streams(1u32.into())
+        .max_concurrent_uni_streams(0u32.into());
+    Arc::new(transport)
+}
+
+/// QUIC server config using the node certificate.
+/// Connection migration is enabled so roaming clients keep their links.
+pub(crate) fn server_config(identity: &TlsIdentity) -> Result<quinn::ServerConfig, String> {
+    let crypto = QuicServerConfig::try_from(identity.server_config())
+        .map_err(|e| format!("QUIC server config: {}", e))?;
+    let mut config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+    config.transport_config(transport_config()).migration(true);
+    Ok(config)
+}
+
+/// QUIC client config for an outbound link (see `TlsIdentity::client_config`).
+pub(crate) fn client_config(
+    identity: &TlsIdentity,
+    pinned_keys: &[[u8; 32]],
+) -> Result<quinn::ClientConfig, String> {
+    let crypto = QuicClientConfig::try_from(identity.client_config(pinned_keys)?)
+        .map_err(|e| format!("QUIC client config: {}", e))?;
+    let mut config = quinn::ClientConfig::new(Arc::new(crypto));
+    config.transport_config(transport_config());
+    Ok(config)
+}
+
+/// Dial `addr` and open the link stream, from a socket bound to `source`
+/// if given.
+pub(crate) async fn dial(
+    config: &quinn::ClientConfig,
+    addr: SocketAddr,
+    server_name: &str,
+    source: Option<&Source>,
+) -> Result<LinkConn, String> {
+    let endpoint = match source {
+        Some(source) => {
+            let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))
+                .and_then(|socket| source.bind(&SockRef::from(&socket), &addr).map(|_| socket))
+                .map_err(|e| format!("QUIC bind: {}", e))?;
+            Endpoint::new(EndpointConfig::default(), None, socket.into(), Arc::new(TokioRuntime))
+        }
+        None => {
+            let bind: SocketAddr = if addr.is_ipv6() {
+                (Ipv6Addr::UNSPECIFIED, 0).into()
+            } else {
+                (Ipv4Addr::UNSPECIFIED, 0).into()
+            };
+            Endpoint::client(bind)
+        }
+    }
+    .map_err(|e| format!("QUIC bind: {}", e))?;
+
+    let conn = endpoint
+        .connect_with(config.clone(), addr, server_name)
+        .map_err(|e| format!("QUIC connect: {}", e))?
+        .await
+        .map_err(|e| format!("QUIC connect: {}", e))?;
+    let (send, recv) = conn
+        .open_bi()
+        .await
+        .map_err(|e| format!("QUIC open stream: {}", e))?;
+
+    Ok(LinkConn {
+        remote: Some(conn.remote_address()),
+        stream: Box::new(QuicStream {
+            send,
+            recv,
+            _conn: conn,
+            _endpoint: Some(endpoint),
+        }),
+        tls: None,
+    })
+}
+
+/// Complete an incoming QUIC connection and accept its link stream.
+pub(crate) async fn accept(incoming: Incoming) -> Result<LinkConn, String> {
+    let conn = incoming
+        .await
+        .map_err(|e| format!("QUIC accept: {}", e))?;
+    let (send, recv) = conn
+        .accept_bi()
+        .await
+        .map_err(|e| format!("QUIC accept stream: {}", e))?;
+
+    Ok(LinkConn {
+        remote: Some(conn.remote_address()),
+        stream: Box::new(QuicStream {
+            send,
+            recv,
+            _conn: conn,
+            _endpoint: None,
+        }),
+        tls: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_stream_roundtrip() {
+        let server_key = SigningKey::generate(&mut OsRng);
+        let server_id = TlsIdentity::new(&server_key).unwrap();
+        let client_id = TlsIdentity::new(&SigningKey::generate(&mut OsRng)).unwrap();
+
+        let endpoint = Endpoint::server(
+            server_config(&server_id).unwrap(),
+            (Ipv4Addr::LOCALHOST, 0).into(),
+        )
+        .unwrap();
+        let target = endpoint.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let incoming = endpoint.accept().await.unwrap();
+            let mut link = accept(incoming).await.unwrap();
+            let mut buf = [0u8; 4];
+            link.stream.read_exact(&mut buf).await.unwrap();
+            link.stream.write_all(&buf).await.unwrap();
+            link.stream.flush().await.unwrap();
+            // Keep the link open until the client has read the echo.
+            let _ = link.stream.read(&mut buf).await;
+        });
+
+        let config = client_config(&client_id, &[server_key.verifying_key().to_bytes()]).unwrap();
+        let mut link = dial(&config, target, "127.0.0.1", None).await.unwrap();
+        link.stream.write_all(b"ping").await.unwrap();
+        link.stream.flush().await.unwrap();
+        let mut buf = [0u8; 4];
+        link.stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        drop(link);
+        server.await.unwrap();
+    }
+}
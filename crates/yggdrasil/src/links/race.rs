@@ -0,0 +1,134 @@
+//! Happy Eyeballs (RFC 8305) dialing across all addresses of a peer.
+//!
+//! Candidates alternate between IPv6 and IPv4, with the address that last
+//! worked tried first. A new attempt starts every 250ms, or as soon as the
+//! previous one fails, and the first connection to come up wins; the
+//! others are dropped.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+
+/// Head start each attempt gets before the next one is started.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Sort candidates for racing: `preferred` first if still present, then
+/// alternating address families, starting with IPv6.
+pub(crate) fn order(addrs: Vec<SocketAddr>, preferred: Option<SocketAddr>) -> Vec<SocketAddr> {
+    let (mut v6, mut v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(|a| a.is_ipv6());
+    let first = preferred.filter(|p| v6.contains(p) || v4.contains(p));
+    v6.retain(|a| Some(*a) != first);
+    v4.retain(|a| Some(*a) != first);
+
+    let mut ordered: Vec<_> = first.into_iter().collect();
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Race `connect` over `addrs` (in order), giving each attempt up to
+/// `attempt_timeout`. Returns the first connection and its address, or the
+/// last error if every attempt failed.
+pub(crate) async fn race<T, F, Fut>(
+    addrs: &[SocketAddr],
+    attempt_timeout: Duration,
+    connect: F,
+) -> Result<(T, SocketAddr), String>
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let mut pending = addrs.iter().copied().peekable();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_err = "no address to dial".to_string();
+
+    loop {
+        if let Some(addr) = pending.next() {
+            let attempt = tokio::time::timeout(attempt_timeout, connect(addr));
+            attempts.push(async move {
+                let result = attempt.await.unwrap_or_else(|_| Err("timed out".to_string()));
+                (addr, result)
+            });
+        }
+        if attempts.is_empty() {
+            return Err(last_err);
+        }
+
+        tokio::select! {
+            Some((addr, result)) = attempts.next() => match result {
+                Ok(conn) => return Ok((conn, addr)),
+                Err(e) => {
+                    tracing::debug!("Connection attempt to {} failed: {}", addr, e);
+                    last_err = format!("{}: {}", addr, e);
+                }
+            },
+            _ = tokio::time::sleep(CONNECTION_ATTEMPT_DELAY), if pending.peek().is_some() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_order() {
+        let addrs = vec![
+            addr("192.0.2.1:1"),
+            addr("192.0.2.2:1"),
+            addr("[2001:db8::1]:1"),
+            addr("[2001:db8::2]:1"),
+            addr("[2001:db8::3]:1"),
+        ];
+        assert_eq!(
+            order(addrs.clone(), None),
+            [addrs[2], addrs[0], addrs[3], addrs[1], addrs[4]]
+        );
+        assert_eq!(
+            order(addrs.clone(), Some(addrs[1])),
+            [addrs[1], addrs[2], addrs[0], addrs[3], addrs[4]]
+        );
+        // A remembered address that no longer resolves is ignored
+        assert_eq!(order(addrs[..1].to_vec(), Some(addrs[4])), [addrs[0]]);
+    }
+
+    #[tokio::test]
+    async fn test_race_falls_back_past_a_stalled_address() {
+        let broken = addr("[2001:db8::1]:1");
+        let working = addr("192.0.2.1:1");
+        let start = Instant::now();
+        let (conn, won) = race(&[broken, working], Duration::from_secs(5), |a| async move {
+            if a == broken {
+                // Blackholed AAAA: never answers
+                std::future::pending::<()>().await;
+            }
+            Ok(a.port())
+        })
+        .await
+        .unwrap();
+        assert_eq!((conn, won), (1, working));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_race_reports_last_error() {
+        let addrs = [addr("[2001:db8::1]:1"), addr("192.0.2.1:1")];
+        let err = race(&addrs, Duration::from_secs(5), |a| async move {
+            Err::<(), _>(format!("refused by {}", a.ip()))
+        })
+        .await
+        .unwrap_err();
+        assert!(err.contains("refused"));
+    }
+}
@@ -0,0 +1,296 @@
+//! Serial device transport (`serial:///dev/ttyUSB0?baud=9600`).
+//!
+//! Meant for packet radio TNCs and other byte-serial links. The link stream
+//! is cut into KISS (default) or SLIP frames, chosen with `framing=`. Both
+//! ends dial their own device; there is no listener. Pair with
+//! `crypto_mode = "signed"` where encryption is not allowed.
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_serial::{SerialPortBuilderExt, SerialStream};
+
+const DEFAULT_BAUD: u32 = 9600;
+
+/// Largest payload put in one frame; 256 bytes is the usual TNC `PACLEN`.
+const MAX_FRAME_PAYLOAD: usize = 256;
+
+/// Frames longer than this are line noise and get dropped.
+const MAX_RECEIVE_FRAME: usize = 64 * 1024;
+
+/// Frame delimiter and escapes, shared by SLIP (RFC 1055) and KISS.
+const FEND: u8 = 0xC0;
+const FESC: u8 = 0xDB;
+const TFEND: u8 = 0xDC;
+const TFESC: u8 = 0xDD;
+
+/// KISS command byte for a data frame on TNC port 0.
+const KISS_DATA: u8 = 0x00;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Framing {
+    Slip,
+    Kiss,
+}
+
+/// Device and settings taken from a `serial://` URI.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SerialDevice {
+    pub(crate) path: String,
+    baud: u32,
+    framing: Framing,
+}
+
+impl SerialDevice {
+    pub(crate) fn parse(url: &url::Url) -> Result<Self, String> {
+        if url.host_str().is_some_and(|h| !h.is_empty()) {
+            return Err("serial URI must not have a host, use serial:///dev/ttyX".to_string());
+        }
+        let path = url.path().to_string();
+        if path.is_empty() || path == "/" {
+            return Err("missing serial device path".to_string());
+        }
+
+        let mut device = Self {
+            path,
+            baud: DEFAULT_BAUD,
+            framing: Framing::Kiss,
+        };
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "baud" => {
+                    device.baud = value
+                        .parse()
+                        .map_err(|e| format!("invalid baud: {}", e))?;
+                }
+                "framing" => {
+                    device.framing = match value.as_ref() {
+                        "kiss" => Framing::Kiss,
+                        "slip" => Framing::Slip,
+                        other => return Err(format!("unknown framing: {} (use kiss or slip)", other)),
+                    };
+                }
+                _ => {}
+            }
+        }
+        Ok(device)
+    }
+
+    /// Open the device and wrap it in the configured framing.
+    pub(crate) fn open(&self) -> Result<FramedStream<SerialStream>, String> {
+        let port = tokio_serial::new(&self.path, self.baud)
+            .open_native_async()
+            .map_err(|e| format!("open {}: {}", self.path, e))?;
+        Ok(FramedStream::new(port, self.framing))
+    }
+}
+
+/// Carries a byte stream over SLIP or KISS frames. Each write becomes at
+/// most one frame; received frames are concatenated back into a stream.
+pub(crate) struct FramedStream<S> {
+    inner: S,
+    framing: Framing,
+    /// Encoded bytes not yet accepted by `inner`.
+    out: VecDeque<u8>,
+    /// Decoded payload not yet returned to the reader.
+    ready: VecDeque<u8>,
+    /// Frame currently being received.
+    frame: Vec<u8>,
+    escaped: bool,
+}
+
+impl<S> FramedStream<S> {
+    pub(crate) fn new(inner: S, framing: Framing) -> Self {
+        Self {
+            inner,
+            framing,
+            out: VecDeque::new(),
+            ready: VecDeque::new(),
+            frame: Vec::new(),
+            escaped: false,
+        }
+    }
+
+    fn encode(&mut self, payload: &[u8]) {
+        self.out.push_back(FEND);
+        if self.framing == Framing::Kiss {
+            self.out.push_back(KISS_DATA);
+        }
+        for &b in payload {
+            match b {
+                FEND => self.out.extend([FESC, TFEND]),
+                FESC => self.out.extend([FESC, TFESC]),
+                _ => self.out.push_back(b),
+            }
+        }
+        self.out.push_back(FEND);
+    }
+
+    fn decode(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            match (b, self.escaped) {
+                (FEND, _) => {
+                    self.escaped = false;
+                    self.end_frame();
+                }
+                (FESC, false) => self.escaped = true,
+                (TFEND, true) => self.push(FEND),
+                (TFESC, true) => self.push(FESC),
+                // Invalid escape: keep the byte, as RFC 1055 suggests
+                _ => self.push(b),
+            }
+        }
+    }
+
+    fn push(&mut self, b: u8) {
+        self.escaped = false;
+        if self.frame.len() < MAX_RECEIVE_FRAME {
+            self.frame.push(b);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        let frame = std::mem::take(&mut self.frame);
+        if frame.len() >= MAX_RECEIVE_FRAME {
+            return;
+        }
+        let payload = match self.framing {
+            Framing::Slip => &frame[..],
+            // Only data frames for port 0 carry the link; skip TNC commands
+            Framing::Kiss => match frame.split_first() {
+                Some((&KISS_DATA, rest)) => rest,
+                _ => return,
+            },
+        };
+        self.ready.extend(payload);
+    }
+}
+
+impl<S: AsyncWrite + Unpin> FramedStream<S> {
+    /// Push encoded bytes into `inner` until done or it would block.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        while !self.out.is_empty() {
+            let (chunk, _) = self.out.as_slices();
+            match Pin::new(&mut self.inner).poll_write(cx, chunk) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into())),
+                Poll::Ready(Ok(n)) => {
+                    self.out.drain(..n);
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for FramedStream<S> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        loop {
+            if !self.ready.is_empty() {
+                let n = self.ready.len().min(buf.remaining());
+                let (chunk, _) = self.ready.as_slices();
+                let n = n.min(chunk.len());
+                buf.put_slice(&chunk[..n]);
+                self.ready.drain(..n);
+                return Poll::Ready(Ok(()));
+            }
+            let mut raw = [0u8; 512];
+            let mut raw_buf = ReadBuf::new(&mut raw);
+            match Pin::new(&mut self.inner).poll_read(cx, &mut raw_buf) {
+                Poll::Ready(Ok(())) if raw_buf.filled().is_empty() => return Poll::Ready(Ok(())),
+                Poll::Ready(Ok(())) => {
+                    let filled = raw_buf.filled().to_vec();
+                    self.decode(&filled);
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for FramedStream<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        // Bound buffering to one frame: finish the previous one first
+        if self.poll_drain(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        let n = buf.len().min(MAX_FRAME_PAYLOAD);
+        self.encode(&buf[..n]);
+        // Start sending now; flush will finish the job
+        let _ = self.poll_drain(cx)?;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        if self.poll_drain(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        if self.poll_drain(cx)?.is_pending() {
+            return Poll::Pending;
+        }
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_parse() {
+        let url = url::Url::parse("serial:///dev/ttyUSB0?baud=1200&framing=slip").unwrap();
+        let device = SerialDevice::parse(&url).unwrap();
+        assert_eq!(device.path, "/dev/ttyUSB0");
+        assert_eq!(device.baud, 1200);
+        assert_eq!(device.framing, Framing::Slip);
+
+        let url = url::Url::parse("serial:///dev/ttyS0").unwrap();
+        let device = SerialDevice::parse(&url).unwrap();
+        assert_eq!((device.baud, device.framing), (DEFAULT_BAUD, Framing::Kiss));
+
+        let url = url::Url::parse("serial:///dev/ttyS0?framing=ax25").unwrap();
+        assert!(SerialDevice::parse(&url).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_kiss_encoding_escapes_and_skips_commands() {
+        let (a, mut b) = tokio::io::duplex(4096);
+        let mut framed = FramedStream::new(a, Framing::Kiss);
+        framed.write_all(&[1, FEND, FESC, 2]).await.unwrap();
+        framed.flush().await.unwrap();
+
+        let mut raw = [0u8; 9];
+        b.read_exact(&mut raw).await.unwrap();
+        assert_eq!(raw, [FEND, KISS_DATA, 1, FESC, TFEND, FESC, TFESC, 2, FEND]);
+
+        // A TXDELAY command frame is ignored, the data frame is delivered
+        b.write_all(&[FEND, 0x01, 50, FEND, FEND, KISS_DATA, FESC, TFEND, 7, FEND]).await.unwrap();
+        let mut buf = [0u8; 2];
+        framed.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [FEND, 7]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pty_roundtrip() {
+        let (master, slave) = SerialStream::pair().unwrap();
+        let mut a = FramedStream::new(master, Framing::Slip);
+        let mut b = FramedStream::new(slave, Framing::Slip);
+
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        a.write_all(&data).await.unwrap();
+        a.flush().await.unwrap();
+        let mut got = vec![0u8; data.len()];
+        b.read_exact(&mut got).await.unwrap();
+        assert_eq!(got, data);
+    }
+}
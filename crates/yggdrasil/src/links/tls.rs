@@ -0,0 +1,305 @@
+//! TLS transport (`tls://`).
+//!
+//! Mirrors yggdrasil-go: every node presents a self-signed certificate
+//! generated from its own Ed25519 key, and no CA validation is performed.
+//! Peers are authenticated by the metadata handshake that follows; a `?key=`
+//! pin is additionally checked against the certificate's public key.
+
+use std::sync::Arc;
+
+use ed25519_dalek::SigningKey;
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair, PKCS_ED25519};
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{
+    ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider,
+};
+use tokio_rustls::rustls::pki_types::{
+    CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime,
+};
+use tokio_rustls::rustls::server::ParsedCertificate;
+use tokio_rustls::rustls::{
+    ClientConfig, DigitallySignedStruct, Error as TlsError, ServerConfig, SignatureScheme,
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use ironwood::types::AsyncConn;
+
+/// PKCS#8 v1 prefix for a bare Ed25519 private key (RFC 8410); the 32-byte
+/// seed follows.
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// DER prefix of an Ed25519 SubjectPublicKeyInfo; the 32-byte key follows.
+const ED25519_SPKI_PREFIX: [u8; 12] = [
+    0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00,
+];
+
+/// Self-signed certificate and key derived from the node's Ed25519 key.
+pub(crate) struct TlsIdentity {
+    cert: CertificateDer<'static>,
+    key: PrivatePkcs8KeyDer<'static>,
+    server: Arc<ServerConfig>,
+}
+
+impl TlsIdentity {
+    /// Generate the node certificate, the same way yggdrasil-go does:
+    /// CN is the hex public key, valid for one year from now.
+    pub(crate) fn new(signing_key: &SigningKey) -> Result<Self, String> {
+        let mut pkcs8 = ED25519_PKCS8_PREFIX.to_vec();
+        pkcs8.extend_from_slice(signing_key.as_bytes());
+        let key = PrivatePkcs8KeyDer::from(pkcs8);
+
+        let key_pair = KeyPair::from_pkcs8_der_and_sign_algo(&key, &PKCS_ED25519)
+            .map_err(|e| format!("TLS key: {}", e))?;
+
+        let mut params = CertificateParams::default();
+        let mut name = DistinguishedName::new();
+        name.push(
+            DnType::CommonName,
+            hex::encode(signing_key.verifying_key().as_bytes()),
+        );
+        params.distinguished_name = name;
+        params.not_before = time::OffsetDateTime::now_utc();
+        params.not_after = params.not_before + time::Duration::days(365);
+
+        let cert = params
+            .self_signed(&key_pair)
+            .map_err(|e| format!("TLS certificate: {}", e))?
+            .der()
+            .clone();
+
+        let server = ServerConfig::builder_with_provider(provider())
+            .with_protocol_versions(&[&tokio_rustls::rustls::version::TLS13])
+            .map_err(|e| format!("TLS server config: {}", e))?
+            .with_no_client_auth()
+            .with_single_cert(vec![cert.clone()], PrivateKeyDer::Pkcs8(key.clone_key()))
+            .map_err(|e| format!("TLS server config: {}", e))?;
+
+        Ok(Self {
+            cert,
+            key,
+            server: Arc::new(server),
+        })
+    }
+
+    /// Acceptor for inbound TLS links.
+    pub(crate) fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.server.clone())
+    }
+
+    /// Connector for an outbound TLS link. If `pinned_keys` is non-empty the
+    /// remote certificate must carry one of them.
+    pub(crate) fn connector(&self, pinned_keys: &[[u8; 32]]) -> Result<TlsConnector, String> {
+        Ok(TlsConnector::from(self.client_config(pinned_keys)?))
+    }
+
+    /// Server-side rustls config, shared by TLS and QUIC listeners.
+    pub(crate) fn server_config(&self) -> Arc<ServerConfig> {
+        self.server.clone()
+    }
+
+    /// Client-side rustls config for an outbound link (see `connector`).
+    pub(crate) fn client_config(&self, pinned_keys: &[[u8; 32]]) -> Result<Arc<ClientConfig>, String> {
+        let verifier = Arc::new(PinnedKeyVerifier {
+            pinned_keys: pinned_keys.to_vec(),
+            provider: provider(),
+        });
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_protocol_versions(&[&tokio_rustls::rustls::version::TLS13])
+            .map_err(|e| format!("TLS client config: {}", e))?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier)
+            .with_client_auth_cert(vec![self.cert.clone()], PrivateKeyDer::Pkcs8(self.key.clone_key()))
+            .map_err(|e| format!("TLS client config: {}", e))?;
+        Ok(Arc::new(config))
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// TLS step performed on a raw stream before the metadata handshake.
+pub(crate) enum TlsUpgrade {
+    Client(TlsConnector, ServerName<'static>),
+    Server(TlsAcceptor),
+}
+
+impl TlsUpgrade {
+    /// Run the TLS handshake and return the encrypted stream.
+    pub(crate) async fn upgrade(
+        self,
+        stream: Box<dyn AsyncConn>,
+    ) -> std::io::Result<Box<dyn AsyncConn>> {
+        match self {
+            TlsUpgrade::Client(connector, server_name) => {
+                Ok(Box::new(connector.connect(server_name, stream).await?))
+            }
+            TlsUpgrade::Server(acceptor) => Ok(Box::new(acceptor.accept(stream).await?)),
+        }
+    }
+}
+
+/// Server name to send for an outbound link: the `sni=` option if given,
+/// otherwise the URI host (IP hosts are accepted but produce no SNI).
+pub(crate) fn server_name(host: &str, sni: Option<&str>) -> Result<ServerName<'static>, String> {
+    let name = sni.unwrap_or(host);
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    ServerName::try_from(name.to_string()).map_err(|e| format!("invalid TLS server name: {}", e))
+}
+
+/// Read the Ed25519 public key from a certificate's SubjectPublicKeyInfo,
+/// if the certificate parses and carries one.
+fn certificate_key(cert: &CertificateDer<'_>) -> Option<[u8; 32]> {
+    let spki = ParsedCertificate::try_from(cert).ok()?.subject_public_key_info();
+    spki.strip_prefix(&ED25519_SPKI_PREFIX)?.try_into().ok()
+}
+
+/// Accepts any self-signed certificate, optionally restricted to pinned keys.
+/// Handshake signatures are still verified against the presented certificate.
+#[derive(Debug)]
+struct PinnedKeyVerifier {
+    pinned_keys: Vec<[u8; 32]>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedKeyVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        if self.pinned_keys.is_empty() {
+            return Ok(ServerCertVerified::assertion());
+        }
+        match certificate_key(end_entity) {
+            Some(key) if self.pinned_keys.contains(&key) => Ok(ServerCertVerified::assertion()),
+            _ => Err(TlsError::General(
+                "certificate key not in pinned keys".to_string(),
+            )),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn handshake(
+        server_key: &SigningKey,
+        pinned: &[[u8; 32]],
+    ) -> std::io::Result<()> {
+        let server = TlsIdentity::new(server_key).unwrap();
+        let client = TlsIdentity::new(&SigningKey::generate(&mut OsRng)).unwrap();
+        let (a, b) = tokio::io::duplex(64 * 1024);
+
+        let accept = tokio::spawn(async move {
+            let mut s = TlsUpgrade::Server(server.acceptor())
+                .upgrade(Box::new(b))
+                .await?;
+            let mut buf = [0u8; 4];
+            s.read_exact(&mut buf).await?;
+            s.write_all(&buf).await?;
+            s.flush().await
+        });
+
+        let connector = client.connector(pinned).unwrap();
+        let name = server_name("192.0.2.1", None).unwrap();
+        let mut c = TlsUpgrade::Client(connector, name)
+            .upgrade(Box::new(a))
+            .await?;
+        c.write_all(b"ping").await?;
+        c.flush().await?;
+        let mut buf = [0u8; 4];
+        c.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"ping");
+        accept.await.unwrap()
+    }
+
+    #[test]
+    fn test_certificate_carries_node_key() {
+        let key = SigningKey::generate(&mut OsRng);
+        let identity = TlsIdentity::new(&key).unwrap();
+        assert_eq!(
+            certificate_key(&identity.cert),
+            Some(key.verifying_key().to_bytes())
+        );
+    }
+
+    #[test]
+    fn test_certificate_key_requires_certificate() {
+        let mut bytes = ED25519_SPKI_PREFIX.to_vec();
+        bytes.extend_from_slice(&[7u8; 32]);
+        assert_eq!(certificate_key(&CertificateDer::from(bytes)), None);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_unpinned() {
+        let key = SigningKey::generate(&mut OsRng);
+        handshake(&key, &[]).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_pinned() {
+        let key = SigningKey::generate(&mut OsRng);
+        handshake(&key, &[key.verifying_key().to_bytes()])
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_wrong_pin_fails() {
+        let key = SigningKey::generate(&mut OsRng);
+        assert!(handshake(&key, &[[7u8; 32]]).await.is_err());
+    }
+
+    #[test]
+    fn test_server_name() {
+        assert!(server_name("example.com", None).is_ok());
+        assert!(server_name("[2001:db8::1]", None).is_ok());
+        assert!(server_name("192.0.2.1", Some("example.org")).is_ok());
+        assert!(server_name("192.0.2.1", Some("not a name")).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Unix domain socket transport (`unix:///path/to/sock`).
+//!
+//! Lets co-located daemons (e.g. containers sharing a volume) peer without
+//! exposing a TCP port. Unix links have no IP address, so they are never
+//! subject to the IP ban list.
+
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use tokio::net::{UnixListener, UnixStream};
+
+/// Default permissions for a listening socket file (owner and group).
+pub(crate) const DEFAULT_SOCKET_MODE: u32 = 0o660;
+
+/// Removes the socket file when the listener goes away.
+pub(crate) struct SocketFileGuard(PathBuf);
+
+impl Drop for SocketFileGuard {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Bind a listening socket at `path`, replacing a stale socket file left
+/// behind by a previous run, and apply `mode` to the socket file.
+pub(crate) async fn bind(path: &Path, mode: u32) -> Result<(UnixListener, SocketFileGuard), String> {
+    remove_stale(path).await?;
+
+    let listener = UnixListener::bind(path).map_err(|e| format!("bind failed: {}", e))?;
+    let guard = SocketFileGuard(path.to_path_buf());
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .map_err(|e| format!("chmod {:o} failed: {}", mode, e))?;
+
+    Ok((listener, guard))
+}
+
+/// Remove `path` if it is a socket nobody is listening on any more.
+/// Refuses to touch regular files or sockets that are still in use.
+async fn remove_stale(path: &Path) -> Result<(), String> {
+    let meta = match std::fs::symlink_metadata(path) {
+        Ok(meta) => meta,
+        Err(_) => return Ok(()),
+    };
+    if !meta.file_type().is_socket() {
+        return Err(format!("{} exists and is not a socket", path.display()));
+    }
+    if UnixStream::connect(path).await.is_ok() {
+        return Err(format!("{} is already in use", path.display()));
+    }
+    tracing::debug!("Removing stale socket file {}", path.display());
+    std::fs::remove_file(path).map_err(|e| format!("remove stale socket: {}", e))
+}
+
+/// Give the socket file at `path` to `group`, a group name or numeric ID,
+/// so that group's members can connect when the mode allows it.
+pub(crate) fn set_group(path: &Path, group: &str) -> Result<(), String> {
+    let gid = group_id(group)?;
+    std::os::unix::fs::chown(path, None, Some(gid)).map_err(|e| format!("chgrp {} failed: {}", group, e))
+}
+
+/// Numeric ID of a group, looked up in /etc/group unless given as a number.
+fn group_id(group: &str) -> Result<u32, String> {
+    if let Ok(gid) = group.parse() {
+        return Ok(gid);
+    }
+    let groups = std::fs::read_to_string("/etc/group").map_err(|e| format!("read /etc/group: {}", e))?;
+    groups
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let gid = fields.nth(1)?.parse().ok()?;
+            Some((name, gid))
+        })
+        .find(|(name, _)| *name == group)
+        .map(|(_, gid)| gid)
+        .ok_or_else(|| format!("unknown group: {}", group))
+}
+
+/// Socket path from a `unix://` URI.
+pub(crate) fn socket_path(url: &url::Url) -> Result<PathBuf, String> {
+    if url.host_str().is_some_and(|h| !h.is_empty()) {
+        return Err("unix URI must not have a host, use unix:///path".to_string());
+    }
+    let path = url
+        .to_file_path()
+        .map_err(|_| "invalid unix socket path".to_string())?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ygg-test-{}-{}.sock", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_socket() {
+        let path = temp_path("stale");
+        let _ = std::fs::remove_file(&path);
+        // A bound-then-dropped std listener leaves the socket file behind
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let (_listener, guard) = bind(&path, DEFAULT_SOCKET_MODE).await.unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, DEFAULT_SOCKET_MODE);
+
+        drop(guard);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_bind_refuses_live_socket_and_files() {
+        let path = temp_path("live");
+        let _ = std::fs::remove_file(&path);
+        let (_listener, _guard) = bind(&path, DEFAULT_SOCKET_MODE).await.unwrap();
+        assert!(bind(&path, DEFAULT_SOCKET_MODE).await.is_err());
+
+        let file = temp_path("file");
+        std::fs::write(&file, b"").unwrap();
+        assert!(bind(&file, DEFAULT_SOCKET_MODE).await.is_err());
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn test_group_id() {
+        assert_eq!(group_id("1234").unwrap(), 1234);
+        assert!(group_id("no-such-group-ygg").is_err());
+        if std::fs::read_to_string("/etc/group").is_ok_and(|g| g.lines().any(|l| l.starts_with("root:"))) {
+            assert_eq!(group_id("root").unwrap(), 0);
+        }
+    }
+
+    #[test]
+    fn test_socket_path() {
+        let url = url::Url::parse("unix:///run/ygg.sock").unwrap();
+        assert_eq!(socket_path(&url).unwrap(), PathBuf::from("/run/ygg.sock"));
+        let url = url::Url::parse("unix://host/run/ygg.sock").unwrap();
+        assert!(socket_path(&url).is_err());
+    }
+}
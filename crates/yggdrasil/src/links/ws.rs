@@ -0,0 +1,236 @@
+//! WebSocket transport (`ws://`, `wss://`).
+//!
+//! The link byte stream is carried in binary WebSocket messages using the
+//! `ygg-ws` subprotocol, like yggdrasil-go. The URI path is the HTTP path,
+//! so a listener can sit behind a reverse proxy on a shared port.
+
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_util::{Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+use ironwood::types::AsyncConn;
+
+const SUBPROTOCOL: &str = "ygg-ws";
+const SUBPROTOCOL_HEADER: &str = "Sec-WebSocket-Protocol";
+const FORWARDED_FOR_HEADER: &str = "X-Forwarded-For";
+
+/// Adapts a WebSocket to a byte stream: each write becomes one binary
+/// message, reads drain received binary messages in order.
+pub(crate) struct WsStream {
+    inner: WebSocketStream<Box<dyn AsyncConn>>,
+    /// Unread remainder of the last received message.
+    pending: bytes::Bytes,
+}
+
+impl WsStream {
+    fn new(inner: WebSocketStream<Box<dyn AsyncConn>>) -> Self {
+        Self {
+            inner,
+            pending: bytes::Bytes::new(),
+        }
+    }
+}
+
+fn ws_error(e: tokio_tungstenite::tungstenite::Error) -> std::io::Error {
+    std::io::Error::other(e)
+}
+
+impl AsyncRead for WsStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        loop {
+            if !self.pending.is_empty() {
+                let n = self.pending.len().min(buf.remaining());
+                let chunk = self.pending.split_to(n);
+                buf.put_slice(&chunk);
+                return Poll::Ready(Ok(()));
+            }
+            match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(Message::Binary(data)))) => self.pending = data,
+                // Control frames are answered by tungstenite; text is not ours
+                Poll::Ready(Some(Ok(Message::Close(_)))) | Poll::Ready(None) => {
+                    return Poll::Ready(Ok(()));
+                }
+                Poll::Ready(Some(Ok(_))) => continue,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(ws_error(e))),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl AsyncWrite for WsStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        match Pin::new(&mut self.inner).poll_ready(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => return Poll::Ready(Err(ws_error(e))),
+            Poll::Pending => return Poll::Pending,
+        }
+        Pin::new(&mut self.inner)
+            .start_send(Message::binary(buf.to_vec()))
+            .map_err(ws_error)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx).map_err(ws_error)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx).map_err(ws_error)
+    }
+}
+
+/// Run the client handshake for `url` (query and fragment are not sent).
+pub(crate) async fn connect(
+    url: &url::Url,
+    stream: Box<dyn AsyncConn>,
+) -> Result<WsStream, String> {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+
+    let mut request = url
+        .as_str()
+        .into_client_request()
+        .map_err(|e| format!("WebSocket request: {}", e))?;
+    request
+        .headers_mut()
+        .insert(SUBPROTOCOL_HEADER, HeaderValue::from_static(SUBPROTOCOL));
+
+    let (ws, _) = tokio_tungstenite::client_async(request, stream)
+        .await
+        .map_err(|e| format!("WebSocket handshake: {}", e))?;
+    Ok(WsStream::new(ws))
+}
+
+/// Run the server handshake, accepting only `ygg-ws` upgrades on `path`.
+///
+/// Returns the stream and the remote address to use for the link. When the
+/// TCP peer is a loopback reverse proxy, the first `X-Forwarded-For` address
+/// is used instead so that bans hit the real client, not the proxy.
+pub(crate) async fn accept(
+    stream: Box<dyn AsyncConn>,
+    path: &str,
+    remote: SocketAddr,
+) -> Result<(WsStream, SocketAddr), String> {
+    let mut forwarded = None;
+    let callback = UpgradeCheck {
+        path,
+        forwarded: &mut forwarded,
+    };
+
+    let ws = tokio_tungstenite::accept_hdr_async(stream, callback)
+        .await
+        .map_err(|e| format!("WebSocket handshake: {}", e))?;
+
+    let remote = match forwarded {
+        Some(ip) if remote.ip().is_loopback() => SocketAddr::new(ip, 0),
+        _ => remote,
+    };
+    Ok((WsStream::new(ws), remote))
+}
+
+/// Validates an upgrade request and records its `X-Forwarded-For` address.
+struct UpgradeCheck<'a> {
+    path: &'a str,
+    forwarded: &'a mut Option<IpAddr>,
+}
+
+impl Callback for UpgradeCheck<'_> {
+    fn on_request(self, req: &Request, mut resp: Response) -> Result<Response, ErrorResponse> {
+        if req.uri().path() != self.path {
+            return Err(error_response(StatusCode::NOT_FOUND));
+        }
+        let offers_ygg = req
+            .headers()
+            .get_all(SUBPROTOCOL_HEADER)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|p| p.trim() == SUBPROTOCOL);
+        if !offers_ygg {
+            return Err(error_response(StatusCode::BAD_REQUEST));
+        }
+        *self.forwarded = req
+            .headers()
+            .get(FORWARDED_FOR_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .and_then(|v| v.trim().parse().ok());
+        resp.headers_mut()
+            .insert(SUBPROTOCOL_HEADER, HeaderValue::from_static(SUBPROTOCOL));
+        Ok(resp)
+    }
+}
+
+fn error_response(status: StatusCode) -> ErrorResponse {
+    let mut resp = ErrorResponse::new(None);
+    *resp.status_mut() = status;
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn loopback() -> SocketAddr {
+        "127.0.0.1:1234".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stream_roundtrip() {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let (mut ws, _) = accept(Box::new(b), "/ygg", loopback()).await.unwrap();
+            let mut buf = [0u8; 10];
+            ws.read_exact(&mut buf).await.unwrap();
+            ws.write_all(&buf).await.unwrap();
+            ws.flush().await.unwrap();
+        });
+
+        let url = url::Url::parse("ws://example.com/ygg?key=00").unwrap();
+        let mut ws = connect(&url, Box::new(a)).await.unwrap();
+        // Two writes arrive as two messages but read back as one stream
+        ws.write_all(b"hello").await.unwrap();
+        ws.write_all(b"world").await.unwrap();
+        ws.flush().await.unwrap();
+        let mut buf = [0u8; 10];
+        ws.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"helloworld");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wrong_path_rejected() {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move { accept(Box::new(b), "/ygg", loopback()).await.is_err() });
+        let url = url::Url::parse("ws://example.com/other").unwrap();
+        assert!(connect(&url, Box::new(a)).await.is_err());
+        assert!(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_for_from_proxy() {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let server = tokio::spawn(async move {
+            let (_, remote) = accept(Box::new(b), "/", loopback()).await.unwrap();
+            remote
+        });
+        let mut request = "ws://example.com/".into_client_request().unwrap();
+        let headers = request.headers_mut();
+        headers.insert(SUBPROTOCOL_HEADER, HeaderValue::from_static(SUBPROTOCOL));
+        headers.insert(FORWARDED_FOR_HEADER, HeaderValue::from_static("192.0.2.7, 10.0.0.1"));
+        let _client = tokio_tungstenite::client_async(request, a).await.unwrap();
+        let remote = server.await.unwrap();
+        assert_eq!(remote.ip(), "192.0.2.7".parse::<IpAddr>().unwrap());
+    }
+}
@@ -1,4 +1,5 @@
-use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
 use ed25519_dalek::SigningKey;
 use getopts::Options;
 use time::macros::format_description;
@@ -9,6 +10,8 @@ use yggdrasil::admin::AdminSocket;
 use yggdrasil::config::Config;
 use yggdrasil::core::Core;
 use yggdrasil::ipv6rwc::ReadWriteCloser;
+use yggdrasil::metrics::MetricsServer;
+use yggdrasil::multicast::{Multicast, UdpBeaconSocket};
 use yggdrasil::tun::TunAdapter;
 
 #[tokio::main]
@@ -18,6 +21,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut opts = Options::new();
     opts.optflagopt("g", "genconf", "Generate a new configuration (optionally save to FILE)", "FILE");
     opts.optopt("c", "config", "Config file path (default: yggdrasil.toml)", "FILE");
+    opts.optopt("", "useconffile", "Config file path, TOML or yggdrasil-go HJSON/JSON (same as --config)", "FILE");
+    opts.optflag("", "normaliseconf", "Print the config file as TOML and exit");
+    opts.optflag("", "check-config", "Check the config file for errors and exit");
     opts.optflag("", "autoconf", "Run without a configuration file (use ephemeral keys)");
     opts.optflag("a", "address", "Print the IPv6 address for the given config and exit");
     opts.optflag("s", "subnet", "Print the IPv6 subnet for the given config and exit");
@@ -45,7 +51,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
-    let config_path = matches.opt_str("config").unwrap_or_else(|| "yggdrasil.toml".to_string());
+    let config_path = matches
+        .opt_str("config")
+        .or_else(|| matches.opt_str("useconffile"))
+        .unwrap_or_else(|| "yggdrasil.toml".to_string());
     let autoconf = matches.opt_present("autoconf");
     let address = matches.opt_present("address");
     let subnet = matches.opt_present("subnet");
@@ -67,6 +76,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
 
+    // --normaliseconf: print the config (e.g. an imported yggdrasil-go one)
+    // as TOML, with warnings for what was left out
+    if matches.opt_present("normaliseconf") {
+        let (config, warnings) = Config::read(Path::new(&config_path))?;
+        for warning in warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+
+    // --check-config: validate without starting anything, exit status 1 on
+    // errors
+    if matches.opt_present("check-config") {
+        let text = std::fs::read_to_string(&config_path).map_err(|e| format!("{}: {}", config_path, e))?;
+        let report = yggdrasil::check::check(&text);
+        for warning in &report.warnings {
+            eprintln!("{}: warning: {}", config_path, warning);
+        }
+        for error in &report.errors {
+            eprintln!("{}: error: {}", config_path, error);
+        }
+        if !report.errors.is_empty() {
+            std::process::exit(1);
+        }
+        println!("{}: OK", config_path);
+        return Ok(());
+    }
+
     // Initialize logging
     let filter = EnvFilter::try_new(&loglevel)
         .unwrap_or_else(|_| EnvFilter::new("info"));
@@ -84,9 +122,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = if autoconf {
         Config::default()
     } else if !config_path.is_empty() {
-        let file = File::open(&config_path)?;
-        let config = std::io::read_to_string(file)?;
-        toml::from_str::<Config>(&config)?
+        Config::load(Path::new(&config_path))?
     } else {
         tracing::error!("Please specify --genconf, --config, or --autoconf");
         std::process::exit(1);
@@ -132,6 +168,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("Your IPv6 address is {}", core.address());
     tracing::info!("Your IPv6 subnet is {}", core.subnet());
     tracing::info!("Your public key is {}", hex::encode(core.public_key()));
+    if !autoconf {
+        core.set_config_path(Path::new(&config_path).to_path_buf());
+    }
 
     // Initialize links with core reference
     core.init_links().await;
@@ -139,6 +178,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Start listeners and connect to peers
     core.start().await;
 
+    // Start multicast discovery
+    let _multicast = if config.multicast_interfaces.is_empty() {
+        None
+    } else {
+        let started = match UdpBeaconSocket::new() {
+            Ok(socket) => Multicast::start(core.clone(), &config.multicast_interfaces, Arc::new(socket)).await,
+            Err(e) => Err(format!("beacon socket: {}", e)),
+        };
+        match started {
+            Ok(multicast) => Some(multicast),
+            Err(e) => {
+                tracing::warn!("Failed to start multicast discovery: {}", e);
+                None
+            }
+        }
+    };
+
     // Create IPv6 RWC bridge
     let mtu = core.mtu();
     let rwc = ReadWriteCloser::new(core.clone(), mtu);
@@ -175,6 +231,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
+    // Without a TUN nothing reads from the core; keep reading anyway so
+    // that session protocol packets (NodeInfo) are still answered.
+    if _tun.is_none() {
+        let rwc = rwc.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0u8; 65536];
+            while rwc.read(&mut buf).await.is_ok() {}
+        });
+    }
+
     // Start admin socket
     let _admin = match AdminSocket::new(&config.admin_listen, core.clone()).await {
         Ok(admin) => Some(admin),
@@ -184,17 +250,85 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    // Wait for shutdown signal
+    // Start metrics endpoint
+    let _metrics = match MetricsServer::new(&config.metrics_listen, core.clone()).await {
+        Ok(metrics) => Some(metrics),
+        Err(e) => {
+            tracing::warn!("Failed to start metrics endpoint: {}", e);
+            None
+        }
+    };
+
+    // Wait for shutdown signal, reloading the config on SIGHUP
     tracing::info!("Yggdrasil started. Press Ctrl+C to stop.");
-    tokio::signal::ctrl_c().await?;
+    wait_for_shutdown(&core).await?;
     tracing::info!("Shutting down...");
 
     // Cleanup
     if let Some(admin) = &_admin {
         admin.close();
     }
+    if let Some(metrics) = &_metrics {
+        metrics.close();
+    }
+    if let Some(multicast) = &_multicast {
+        multicast.close().await;
+    }
     core.close().await.ok();
 
     tracing::info!("Goodbye!");
     Ok(())
 }
+
+/// Wait for Ctrl+C. On unix, SIGHUP reloads the config file meanwhile.
+#[cfg(unix)]
+async fn wait_for_shutdown(core: &Core) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => return result,
+            _ = hangup.recv() => {
+                tracing::info!("Reloading configuration");
+                match core.reload_config().await {
+                    Ok(report) => log_reload(&report),
+                    Err(e) => tracing::error!("Failed to reload configuration: {}", e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown(_core: &Core) -> std::io::Result<()> {
+    tokio::signal::ctrl_c().await
+}
+
+#[cfg(unix)]
+fn log_reload(report: &yggdrasil::core::ReloadReport) {
+    for uri in &report.peers_added {
+        tracing::info!("Added peer {}", uri);
+    }
+    for uri in &report.peers_removed {
+        tracing::info!("Removed peer {}", uri);
+    }
+    for addr in &report.listeners_added {
+        tracing::info!("Listening on {}", addr);
+    }
+    for addr in &report.listeners_removed {
+        tracing::info!("Stopped listening on {}", addr);
+    }
+    if report.allowed_public_keys_changed {
+        tracing::info!("Updated allowed public keys");
+    }
+    if report.node_info_changed {
+        tracing::info!("Updated node info");
+    }
+    for e in &report.errors {
+        tracing::error!("Reload: {}", e);
+    }
+    if !report.restart_required.is_empty() {
+        tracing::warn!("Restart to apply changes to: {}", report.restart_required.join(", "));
+    }
+}
@@ -0,0 +1,372 @@
+//! Prometheus metrics endpoint (`metrics_listen`).
+//!
+//! A minimal HTTP server answering `GET /metrics` with the node's state in
+//! the Prometheus text format: traffic per link, routing table, tree and
+//! path cache sizes, sessions, queue drops, bans and failed handshakes.
+//! Every scrape reads the live state, nothing is sampled in between.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+use crate::address::addr_for_key;
+use crate::core::Core;
+use crate::links;
+
+/// Largest request head read before the client is dropped.
+const MAX_REQUEST_SIZE: usize = 8192;
+/// How long a client has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// The metrics HTTP server.
+pub struct MetricsServer {
+    cancel: CancellationToken,
+    handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Start serving metrics on `listen_addr`, "host:port" with an optional
+    /// `http://` prefix. Empty or "none" disables the endpoint.
+    pub async fn new(listen_addr: &str, core: Arc<Core>) -> Result<Self, String> {
+        if listen_addr.is_empty() || listen_addr == "none" {
+            return Ok(Self {
+                cancel: CancellationToken::new(),
+                handle: None,
+            });
+        }
+
+        let addr = listen_addr.strip_prefix("http://").unwrap_or(listen_addr).trim_end_matches('/');
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| format!("metrics bind failed: {}", e))?;
+        let actual_addr = listener
+            .local_addr()
+            .map_err(|e| format!("metrics local_addr: {}", e))?;
+        tracing::info!("Metrics listening on http://{}/metrics", actual_addr);
+
+        let cancel = CancellationToken::new();
+        let handle = tokio::spawn(accept_loop(listener, core, cancel.clone()));
+        Ok(Self {
+            cancel,
+            handle: Some(handle),
+        })
+    }
+
+    /// Stop the metrics server.
+    pub fn close(&self) {
+        self.cancel.cancel();
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+    }
+}
+
+async fn accept_loop(listener: TcpListener, core: Arc<Core>, cancel: CancellationToken) {
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            result = listener.accept() => {
+                match result {
+                    Ok((stream, _)) => {
+                        tokio::spawn(handle_http(stream, core.clone()));
+                    }
+                    Err(e) => {
+                        tracing::error!("Metrics accept error: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Answer one HTTP request and close the connection.
+async fn handle_http(mut stream: TcpStream, core: Arc<Core>) {
+    let Ok(Some(head)) = tokio::time::timeout(REQUEST_TIMEOUT, read_head(&mut stream)).await else {
+        return;
+    };
+    let request_line = head.lines().next().unwrap_or_default();
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let path = parts.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let (status, body) = match (method, path) {
+        ("GET" | "HEAD", "/metrics") => ("200 OK", Snapshot::collect(&core).await.render()),
+        ("GET" | "HEAD", _) => ("404 Not Found", "metrics are served at /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", "only GET is supported\n".to_string()),
+    };
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        CONTENT_TYPE,
+        body.len()
+    );
+    if method != "HEAD" {
+        response.push_str(&body);
+    }
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Read up to the blank line ending the request head. `None` if the
+/// client closes early or sends too much.
+async fn read_head(stream: &mut TcpStream) -> Option<String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 || buf.len() + n > MAX_REQUEST_SIZE {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    String::from_utf8(buf).ok()
+}
+
+/// Traffic of one link that is up.
+struct LinkSample {
+    uri: String,
+    address: String,
+    inbound: bool,
+    rx_bytes: usize,
+    tx_bytes: usize,
+    rx_rate: usize,
+    tx_rate: usize,
+    latency_ms: Option<f64>,
+}
+
+/// Everything one scrape reports.
+struct Snapshot {
+    links: Vec<LinkSample>,
+    peers_down: usize,
+    routing_entries: usize,
+    tree_depth: usize,
+    paths: usize,
+    lookups_pending: usize,
+    sessions_established: usize,
+    sessions_handshaking: usize,
+    queue_drops: ironwood::QueueDrops,
+    banned: usize,
+    handshake_failures: BTreeMap<&'static str, u64>,
+}
+
+impl Snapshot {
+    async fn collect(core: &Core) -> Self {
+        let peers = core.get_peers().await;
+        let paths = core.get_paths().await;
+        let sessions = core.get_sessions().await;
+        Self {
+            links: peers
+                .iter()
+                .filter(|p| p.up)
+                .map(|p| LinkSample {
+                    uri: p.uri.clone(),
+                    address: p.key.map(|k| addr_for_key(&k).to_string()).unwrap_or_default(),
+                    inbound: p.inbound,
+                    rx_bytes: p.rx_bytes,
+                    tx_bytes: p.tx_bytes,
+                    rx_rate: p.rx_rate,
+                    tx_rate: p.tx_rate,
+                    latency_ms: p.latency_ms,
+                })
+                .collect(),
+            peers_down: peers.iter().filter(|p| !p.up).count(),
+            routing_entries: core.routing_entries().await,
+            tree_depth: core.tree_coordinates().await.len(),
+            paths: paths.iter().filter(|p| !p.path.is_empty()).count(),
+            lookups_pending: paths.iter().filter(|p| p.lookup_pending).count(),
+            sessions_established: sessions.iter().filter(|s| s.established).count(),
+            sessions_handshaking: sessions.iter().filter(|s| !s.established).count(),
+            queue_drops: core.queue_drops().await,
+            banned: core.banned_count().await,
+            handshake_failures: core.handshake_failures(),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = Exposition::default();
+        out.single("yggdrasil_links_up", "gauge", "Links currently up.", self.links.len());
+        out.single(
+            "yggdrasil_peers_down",
+            "gauge",
+            "Configured peers that are not connected.",
+            self.peers_down,
+        );
+
+        let links = &self.links;
+        out.per_link(links, "yggdrasil_link_rx_bytes_total", "counter", "Bytes received over the link.", |l| l.rx_bytes);
+        out.per_link(links, "yggdrasil_link_tx_bytes_total", "counter", "Bytes sent over the link.", |l| l.tx_bytes);
+        out.per_link(
+            links,
+            "yggdrasil_link_rx_rate_bytes",
+            "gauge",
+            "Bytes received over the link in the last second.",
+            |l| l.rx_rate,
+        );
+        out.per_link(
+            links,
+            "yggdrasil_link_tx_rate_bytes",
+            "gauge",
+            "Bytes sent over the link in the last second.",
+            |l| l.tx_rate,
+        );
+        out.family("yggdrasil_link_latency_seconds", "gauge", "Round-trip time of the link, once measured.");
+        for link in &self.links {
+            if let Some(latency) = link.latency_ms {
+                out.sample("yggdrasil_link_latency_seconds", &link.labels(), latency / 1000.0);
+            }
+        }
+
+        out.single("yggdrasil_routing_entries", "gauge", "Tree nodes known to the router.", self.routing_entries);
+        out.single("yggdrasil_tree_depth", "gauge", "Hops from the tree root to this node.", self.tree_depth);
+        out.single("yggdrasil_paths", "gauge", "Paths in the path cache.", self.paths);
+        out.single(
+            "yggdrasil_path_lookups_pending",
+            "gauge",
+            "Path lookups waiting for an answer.",
+            self.lookups_pending,
+        );
+
+        out.family("yggdrasil_sessions", "gauge", "End-to-end encrypted sessions.");
+        out.sample("yggdrasil_sessions", &[("state", "established")], self.sessions_established);
+        out.sample("yggdrasil_sessions", &[("state", "handshake")], self.sessions_handshaking);
+
+        out.family(
+            "yggdrasil_queue_drops_total",
+            "counter",
+            "Packets dropped from full queues: undelivered to the TUN (delivery) or waiting for a slow link (send).",
+        );
+        out.sample("yggdrasil_queue_drops_total", &[("queue", "delivery")], self.queue_drops.delivery);
+        out.sample("yggdrasil_queue_drops_total", &[("queue", "send")], self.queue_drops.send);
+
+        out.single("yggdrasil_banned_ips", "gauge", "IPs banned after repeated failed handshakes.", self.banned);
+
+        out.family("yggdrasil_handshake_failures_total", "counter", "Failed link handshakes.");
+        for (reason, count) in &self.handshake_failures {
+            out.sample("yggdrasil_handshake_failures_total", &[("reason", reason)], count);
+        }
+        out.0
+    }
+}
+
+impl LinkSample {
+    /// The URI is redacted: anyone who can scrape sees the labels.
+    fn labels(&self) -> [(&str, String); 3] {
+        [
+            ("uri", links::redact_uri(&self.uri)),
+            ("address", self.address.clone()),
+            ("direction", if self.inbound { "in" } else { "out" }.to_string()),
+        ]
+    }
+}
+
+/// Prometheus text format being written.
+#[derive(Default)]
+struct Exposition(String);
+
+impl Exposition {
+    /// Start a metric family.
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {} {}", name, help);
+        let _ = writeln!(self.0, "# TYPE {} {}", name, kind);
+    }
+
+    /// Add a sample to the family just started.
+    fn sample<L: AsRef<str>>(&mut self, name: &str, labels: &[(&str, L)], value: impl Display) {
+        self.0.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value.as_ref())))
+                .collect();
+            let _ = write!(self.0, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.0, " {}", value);
+    }
+
+    /// A family with one sample per link.
+    fn per_link(&mut self, links: &[LinkSample], name: &str, kind: &str, help: &str, value: fn(&LinkSample) -> usize) {
+        self.family(name, kind, help);
+        for link in links {
+            self.sample(name, &link.labels(), value(link));
+        }
+    }
+
+    /// A family with a single unlabelled sample.
+    fn single(&mut self, name: &str, kind: &str, help: &str, value: impl Display) {
+        self.family(name, kind, help);
+        self.sample::<&str>(name, &[], value);
+    }
+}
+
+fn escape_label(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let snapshot = Snapshot {
+            links: vec![LinkSample {
+                uri: "socks://user:pass@[::1]:1080/192.0.2.1:9001?password=x".to_string(),
+                address: "200::1".to_string(),
+                inbound: false,
+                rx_bytes: 1500,
+                tx_bytes: 900,
+                rx_rate: 15,
+                tx_rate: 9,
+                latency_ms: Some(12.5),
+            }],
+            peers_down: 1,
+            routing_entries: 7,
+            tree_depth: 2,
+            paths: 3,
+            lookups_pending: 1,
+            sessions_established: 2,
+            sessions_handshaking: 0,
+            queue_drops: ironwood::QueueDrops { delivery: 4, send: 5 },
+            banned: 1,
+            handshake_failures: [("timeout", 6)].into_iter().collect(),
+        };
+        let text = snapshot.render();
+
+        let labels = r#"{uri="socks://[::1]:1080/192.0.2.1:9001",address="200::1",direction="out"}"#;
+        for line in [
+            "# TYPE yggdrasil_link_rx_bytes_total counter".to_string(),
+            format!("yggdrasil_link_rx_bytes_total{} 1500", labels),
+            format!("yggdrasil_link_tx_rate_bytes{} 9", labels),
+            format!("yggdrasil_link_latency_seconds{} 0.0125", labels),
+            "yggdrasil_peers_down 1".to_string(),
+            "yggdrasil_routing_entries 7".to_string(),
+            "yggdrasil_tree_depth 2".to_string(),
+            "yggdrasil_paths 3".to_string(),
+            "yggdrasil_sessions{state=\"established\"} 2".to_string(),
+            "yggdrasil_queue_drops_total{queue=\"send\"} 5".to_string(),
+            "yggdrasil_banned_ips 1".to_string(),
+            "yggdrasil_handshake_failures_total{reason=\"timeout\"} 6".to_string(),
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {:?} in\n{}", line, text);
+        }
+        assert!(!text.contains("pass"), "credentials in\n{}", text);
+
+        // Every sample belongs to a declared family
+        let mut declared = Vec::new();
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("# TYPE ") {
+                declared.push(rest.split(' ').next().unwrap().to_string());
+            } else if !line.starts_with('#') {
+                let name = line.split(['{', ' ']).next().unwrap();
+                assert_eq!(declared.last().map(String::as_str), Some(name));
+            }
+        }
+    }
+}
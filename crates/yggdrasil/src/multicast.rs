@@ -0,0 +1,571 @@
+//! Multicast peer discovery on local networks, compatible with yggdrasil-go.
+//!
+//! On interfaces with `beacon` enabled, a TLS listener is opened on the
+//! interface's link-local address and its port is advertised in a beacon
+//! sent to `[ff02::114]:9001`, every second at first and backing off to
+//! every 15 seconds. Beacons heard on interfaces with `listen` enabled are
+//! answered by dialing the advertised listener with an ephemeral link.
+//!
+//! Beacon format (big endian): major version u16, minor version u16,
+//! public key (32 bytes), port u16, hash length u16, hash. The hash is
+//! BLAKE2b-512 of the public key, keyed with the interface password.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use getifaddrs::InterfaceFlags;
+use regex::Regex;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::MulticastInterfaceConfig;
+use crate::core::Core;
+use crate::links::LinkOptions;
+use crate::version::{blake2b_hash, PROTOCOL_VERSION_MAJOR, PROTOCOL_VERSION_MINOR};
+
+/// Link-local multicast group for beacons.
+pub const GROUP_ADDR: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0x114);
+/// UDP port beacons are sent to.
+pub const GROUP_PORT: u16 = 9001;
+
+/// How often interfaces are rescanned and due beacons sent.
+const TICK: Duration = Duration::from_secs(1);
+/// Beacon interval grows by one second per beacon, up to this.
+const MAX_BEACON_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Fixed part of a beacon: versions, public key, port and hash length.
+const BEACON_HEADER_SIZE: usize = 40;
+
+/// A decoded discovery beacon.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Beacon {
+    pub public_key: [u8; 32],
+    pub port: u16,
+    pub hash: Vec<u8>,
+}
+
+impl Beacon {
+    /// Beacon advertising `port`, hashed with `password`.
+    pub fn new(public_key: [u8; 32], port: u16, password: &str) -> Self {
+        Self {
+            public_key,
+            port,
+            hash: blake2b_hash(&public_key, password.as_bytes()).to_vec(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(BEACON_HEADER_SIZE + self.hash.len());
+        out.extend_from_slice(&PROTOCOL_VERSION_MAJOR.to_be_bytes());
+        out.extend_from_slice(&PROTOCOL_VERSION_MINOR.to_be_bytes());
+        out.extend_from_slice(&self.public_key);
+        out.extend_from_slice(&self.port.to_be_bytes());
+        out.extend_from_slice(&(self.hash.len() as u16).to_be_bytes());
+        out.extend_from_slice(&self.hash);
+        out
+    }
+
+    /// Decode a beacon. Returns `None` if it is malformed or from another
+    /// protocol version.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < BEACON_HEADER_SIZE {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_be_bytes([buf[i], buf[i + 1]]);
+        if u16_at(0) != PROTOCOL_VERSION_MAJOR || u16_at(2) != PROTOCOL_VERSION_MINOR {
+            return None;
+        }
+        let public_key = buf[4..36].try_into().ok()?;
+        let port = u16_at(36);
+        let hash = buf.get(BEACON_HEADER_SIZE..BEACON_HEADER_SIZE + u16_at(38) as usize)?;
+        Some(Self {
+            public_key,
+            port,
+            hash: hash.to_vec(),
+        })
+    }
+
+    /// Whether the sender uses the same discovery password.
+    pub fn check_password(&self, password: &str) -> bool {
+        self.hash == blake2b_hash(&self.public_key, password.as_bytes())
+    }
+}
+
+/// A network interface that can take part in discovery.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interface {
+    pub name: String,
+    pub index: u32,
+    /// Link-local IPv6 addresses; the first one is used for the listener.
+    pub addrs: Vec<Ipv6Addr>,
+}
+
+/// Where beacons are sent and received. Abstracted so that discovery can
+/// run over loopback or veth pairs in tests.
+#[async_trait::async_trait]
+pub trait BeaconSocket: Send + Sync {
+    /// Interfaces currently usable for discovery.
+    fn interfaces(&self) -> io::Result<Vec<Interface>>;
+
+    /// Start receiving beacons on `iface`. Called whenever the interface
+    /// (re)appears, so it must tolerate repeated calls.
+    fn join(&self, iface: &Interface) -> io::Result<()>;
+
+    /// Send a beacon out of `iface`.
+    async fn send(&self, iface: &Interface, beacon: &[u8]) -> io::Result<()>;
+
+    /// Receive a beacon. The sender's scope ID is the index of the
+    /// interface it arrived on.
+    async fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddrV6)>;
+}
+
+/// The real beacon socket: UDP on `[::]:9001`, joined to ff02::114 on
+/// each interface.
+pub struct UdpBeaconSocket {
+    socket: UdpSocket,
+}
+
+impl UdpBeaconSocket {
+    pub fn new() -> io::Result<Self> {
+        let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_only_v6(true)?;
+        // yggdrasil-go (or another instance) may hold the port too
+        socket.set_reuse_address(true)?;
+        #[cfg(unix)]
+        socket.set_reuse_port(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, GROUP_PORT)).into())?;
+        Ok(Self {
+            socket: UdpSocket::from_std(socket.into())?,
+        })
+    }
+}
+
+fn is_link_local(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+#[async_trait::async_trait]
+impl BeaconSocket for UdpBeaconSocket {
+    fn interfaces(&self) -> io::Result<Vec<Interface>> {
+        let mut found: Vec<Interface> = Vec::new();
+        for i in getifaddrs::getifaddrs()? {
+            let usable = InterfaceFlags::UP | InterfaceFlags::RUNNING | InterfaceFlags::MULTICAST;
+            if !i.flags.contains(usable) || i.flags.intersects(InterfaceFlags::LOOPBACK | InterfaceFlags::POINTTOPOINT) {
+                continue;
+            }
+            let (Some(index), Some(IpAddr::V6(ip))) = (i.index, i.address.ip_addr()) else {
+                continue;
+            };
+            if !is_link_local(&ip) {
+                continue;
+            }
+            match found.iter_mut().find(|f| f.name == i.name) {
+                Some(f) => f.addrs.push(ip),
+                None => found.push(Interface {
+                    name: i.name.clone(),
+                    index,
+                    addrs: vec![ip],
+                }),
+            }
+        }
+        Ok(found)
+    }
+
+    fn join(&self, iface: &Interface) -> io::Result<()> {
+        match self.socket.join_multicast_v6(&GROUP_ADDR, iface.index) {
+            Err(e) if e.kind() != io::ErrorKind::AddrInUse => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    async fn send(&self, iface: &Interface, beacon: &[u8]) -> io::Result<()> {
+        let group = SocketAddrV6::new(GROUP_ADDR, GROUP_PORT, 0, iface.index);
+        self.socket.send_to(beacon, group).await.map(|_| ())
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddrV6)> {
+        loop {
+            if let (n, SocketAddr::V6(from)) = self.socket.recv_from(buf).await? {
+                return Ok((n, from));
+            }
+        }
+    }
+}
+
+/// Discovery state of one interface.
+struct InterfaceState {
+    iface: Interface,
+    config: MulticastInterfaceConfig,
+    /// Listener key and bound address, while beaconing.
+    listener: Option<(String, SocketAddr)>,
+    interval: Duration,
+    last_beacon: Option<Instant>,
+}
+
+#[derive(Default)]
+struct State {
+    interfaces: HashMap<String, InterfaceState>,
+    /// Ephemeral links we dialed, by remote key, so a peer is not dialed
+    /// again while its link is still up.
+    dials: HashMap<[u8; 32], JoinHandle<()>>,
+}
+
+/// Running multicast discovery.
+pub struct Multicast {
+    core: Arc<Core>,
+    state: Arc<Mutex<State>>,
+    cancel: CancellationToken,
+}
+
+impl Multicast {
+    /// Start discovery on interfaces matching `configs`.
+    pub async fn start(
+        core: Arc<Core>,
+        configs: &[MulticastInterfaceConfig],
+        socket: Arc<dyn BeaconSocket>,
+    ) -> Result<Self, String> {
+        let mut rules = Vec::with_capacity(configs.len());
+        for config in configs {
+            let regex = Regex::new(&config.regex)
+                .map_err(|e| format!("invalid multicast interface regex {:?}: {}", config.regex, e))?;
+            if config.password.len() > 64 {
+                return Err("multicast password too long (max 64 chars)".to_string());
+            }
+            rules.push((regex, config.clone()));
+        }
+
+        let state = Arc::new(Mutex::new(State::default()));
+        let cancel = CancellationToken::new();
+
+        {
+            let (core, socket, state, cancel) = (core.clone(), socket.clone(), state.clone(), cancel.clone());
+            tokio::spawn(async move {
+                loop {
+                    announce(&core, socket.as_ref(), &rules, &state).await;
+                    tokio::select! {
+                        _ = cancel.cancelled() => break,
+                        _ = tokio::time::sleep(TICK) => {}
+                    }
+                }
+            });
+        }
+        {
+            let (core, state, cancel) = (core.clone(), state.clone(), cancel.clone());
+            tokio::spawn(async move {
+                let mut buf = vec![0u8; 2048];
+                loop {
+                    let received = tokio::select! {
+                        _ = cancel.cancelled() => break,
+                        r = socket.recv(&mut buf) => r,
+                    };
+                    match received {
+                        Ok((n, from)) => handle_beacon(&core, &state, &buf[..n], from).await,
+                        Err(e) => {
+                            tracing::warn!("Multicast receive failed: {}", e);
+                            tokio::time::sleep(TICK).await;
+                        }
+                    }
+                }
+            });
+        }
+
+        Ok(Self { core, state, cancel })
+    }
+
+    /// Stop beaconing and close the discovery listeners.
+    /// Links that are already up are left alone.
+    pub async fn close(&self) {
+        self.cancel.cancel();
+        let mut state = self.state.lock().await;
+        let mut links = self.core.links.lock().await;
+        for (_, iface) in state.interfaces.drain() {
+            if let Some((key, _)) = iface.listener {
+                links.stop_listener(&key);
+            }
+        }
+    }
+}
+
+/// Rescan interfaces, keep listeners in step and send due beacons.
+async fn announce(
+    core: &Arc<Core>,
+    socket: &dyn BeaconSocket,
+    rules: &[(Regex, MulticastInterfaceConfig)],
+    state: &Mutex<State>,
+) {
+    let found = match socket.interfaces() {
+        Ok(found) => found,
+        Err(e) => {
+            tracing::warn!("Failed to list interfaces for multicast: {}", e);
+            Vec::new()
+        }
+    };
+    let mut state = state.lock().await;
+
+    // Forget interfaces that went away or changed index
+    let stale: Vec<String> = state
+        .interfaces
+        .iter()
+        .filter(|(name, s)| !found.iter().any(|f| &f.name == *name && f.index == s.iface.index && !f.addrs.is_empty()))
+        .map(|(name, _)| name.clone())
+        .collect();
+    for name in stale {
+        if let Some(s) = state.interfaces.remove(&name) {
+            tracing::info!("Stopped multicasting on {}", name);
+            if let Some((key, _)) = s.listener {
+                core.links.lock().await.stop_listener(&key);
+            }
+        }
+    }
+
+    for iface in found {
+        let Some((_, config)) = rules.iter().find(|(re, _)| re.is_match(&iface.name)) else {
+            continue;
+        };
+        if (!config.beacon && !config.listen) || iface.addrs.is_empty() {
+            continue;
+        }
+
+        if !state.interfaces.contains_key(&iface.name) {
+            if let Err(e) = socket.join(&iface) {
+                tracing::warn!("Failed to join multicast group on {}: {}", iface.name, e);
+                continue;
+            }
+            tracing::info!("Started multicasting on {}", iface.name);
+        }
+        let s = state
+            .interfaces
+            .entry(iface.name.clone())
+            .or_insert_with(|| InterfaceState {
+                iface: iface.clone(),
+                config: config.clone(),
+                listener: None,
+                interval: Duration::ZERO,
+                last_beacon: None,
+            });
+        s.config = config.clone();
+
+        // The listener's address may have been removed from the interface
+        if let Some((key, bound)) = &s.listener {
+            let still_there = matches!(bound, SocketAddr::V6(a) if iface.addrs.contains(a.ip()));
+            if !still_there || !config.beacon {
+                core.links.lock().await.stop_listener(key);
+                s.listener = None;
+            }
+        }
+        s.iface = iface;
+
+        if !config.beacon {
+            continue;
+        }
+        if s.listener.is_none() {
+            let addr = SocketAddrV6::new(s.iface.addrs[0], config.port, 0, s.iface.index);
+            let options = LinkOptions {
+                priority: config.priority,
+                password: config.password.as_bytes().to_vec(),
+                ..Default::default()
+            };
+            match core.links.lock().await.listen_tls_at(addr.into(), options).await {
+                Ok(listener) => {
+                    s.listener = Some(listener);
+                    s.interval = Duration::ZERO;
+                    s.last_beacon = None;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to start multicast listener on {}: {}", s.iface.name, e);
+                    continue;
+                }
+            }
+        }
+
+        let due = s.last_beacon.is_none_or(|t| t.elapsed() >= s.interval);
+        if let (true, Some((_, bound))) = (due, &s.listener) {
+            let beacon = Beacon::new(core.public_key, bound.port(), &config.password);
+            if let Err(e) = socket.send(&s.iface, &beacon.encode()).await {
+                tracing::debug!("Failed to send beacon on {}: {}", s.iface.name, e);
+            }
+            s.last_beacon = Some(Instant::now());
+            s.interval = (s.interval + TICK).min(MAX_BEACON_INTERVAL);
+        }
+    }
+}
+
+/// Dial the sender of a valid beacon, unless we are already linked to it.
+async fn handle_beacon(core: &Arc<Core>, state: &Mutex<State>, buf: &[u8], from: SocketAddrV6) {
+    let Some(beacon) = Beacon::decode(buf) else {
+        return;
+    };
+    if beacon.public_key == core.public_key {
+        return;
+    }
+
+    let mut state = state.lock().await;
+    state.dials.retain(|_, handle| !handle.is_finished());
+    if state.dials.contains_key(&beacon.public_key) {
+        return;
+    }
+    let Some(s) = state
+        .interfaces
+        .values()
+        .find(|s| s.iface.index == from.scope_id())
+    else {
+        return;
+    };
+    if !s.config.listen || !beacon.check_password(&s.config.password) {
+        return;
+    }
+    if core.get_peers().await.iter().any(|p| p.up && p.key == Some(beacon.public_key)) {
+        return;
+    }
+
+    let addr = SocketAddrV6::new(*from.ip(), beacon.port, 0, from.scope_id());
+    let uri = format!("tls://[{}%{}]:{}", from.ip(), s.iface.name, beacon.port);
+    let options = LinkOptions {
+        pinned_keys: vec![beacon.public_key],
+        priority: s.config.priority,
+        password: s.config.password.as_bytes().to_vec(),
+        ..Default::default()
+    };
+    tracing::debug!("Discovered {} via multicast on {}", hex::encode(beacon.public_key), s.iface.name);
+    let dialed = core.links.lock().await.dial_ephemeral(uri, addr.into(), options);
+    match dialed {
+        Ok(handle) => {
+            state.dials.insert(beacon.public_key, handle);
+        }
+        Err(e) => tracing::warn!("Failed to dial multicast peer: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use ed25519_dalek::SigningKey;
+    use rand::rngs::OsRng;
+
+    /// Beacons over unicast UDP on ::1, sent to every other test socket.
+    struct LoopbackSocket {
+        socket: UdpSocket,
+        peers: std::sync::Mutex<Vec<SocketAddr>>,
+    }
+
+    impl LoopbackSocket {
+        async fn new() -> Self {
+            Self {
+                socket: UdpSocket::bind("[::1]:0").await.unwrap(),
+                peers: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl BeaconSocket for LoopbackSocket {
+        fn interfaces(&self) -> io::Result<Vec<Interface>> {
+            Ok(vec![Interface {
+                name: "lo".to_string(),
+                index: 0,
+                addrs: vec![Ipv6Addr::LOCALHOST],
+            }])
+        }
+
+        fn join(&self, _iface: &Interface) -> io::Result<()> {
+            Ok(())
+        }
+
+        async fn send(&self, _iface: &Interface, beacon: &[u8]) -> io::Result<()> {
+            let peers = self.peers.lock().unwrap().clone();
+            for peer in peers {
+                self.socket.send_to(beacon, peer).await?;
+            }
+            Ok(())
+        }
+
+        async fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddrV6)> {
+            loop {
+                if let (n, SocketAddr::V6(from)) = self.socket.recv_from(buf).await? {
+                    return Ok((n, from));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_beacon_format() {
+        let key = [7u8; 32];
+        let encoded = Beacon::new(key, 0x1234, "").encode();
+        assert_eq!(encoded.len(), BEACON_HEADER_SIZE + 64);
+        assert_eq!(&encoded[..4], &[0, 0, 0, 5]);
+        assert_eq!(&encoded[4..36], &key);
+        assert_eq!(&encoded[36..40], &[0x12, 0x34, 0, 64]);
+
+        let decoded = Beacon::decode(&encoded).unwrap();
+        assert_eq!(decoded.port, 0x1234);
+        assert!(decoded.check_password(""));
+        assert!(!decoded.check_password("secret"));
+
+        // Truncated hash and foreign versions are rejected
+        assert!(Beacon::decode(&encoded[..70]).is_none());
+        let mut other = encoded.clone();
+        other[3] = 4;
+        assert!(Beacon::decode(&other).is_none());
+    }
+
+    #[test]
+    fn test_beacon_password() {
+        let beacon = Beacon::new([1u8; 32], 1, "secret");
+        assert!(beacon.check_password("secret"));
+        assert!(!beacon.check_password(""));
+    }
+
+    async fn node(socket: Arc<LoopbackSocket>) -> (Arc<Core>, Multicast) {
+        let interfaces = [MulticastInterfaceConfig {
+            regex: ".*".to_string(),
+            beacon: true,
+            listen: true,
+            port: 0,
+            priority: 0,
+            password: String::new(),
+        }];
+        let core = Core::new(SigningKey::generate(&mut OsRng), Config::default());
+        core.init_links().await;
+        let multicast = Multicast::start(core.clone(), &interfaces, socket)
+            .await
+            .unwrap();
+        (core, multicast)
+    }
+
+    #[tokio::test]
+    async fn test_discovery_over_loopback() {
+        let sock_a = Arc::new(LoopbackSocket::new().await);
+        let sock_b = Arc::new(LoopbackSocket::new().await);
+        sock_a.peers.lock().unwrap().push(sock_b.socket.local_addr().unwrap());
+        sock_b.peers.lock().unwrap().push(sock_a.socket.local_addr().unwrap());
+
+        let (core_a, multicast_a) = node(sock_a).await;
+        let (core_b, multicast_b) = node(sock_b).await;
+
+        let linked = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                let peers = core_a.get_peers().await;
+                if peers.iter().any(|p| p.up && p.key == Some(core_b.public_key)) {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await;
+        assert!(linked.is_ok(), "nodes did not discover each other");
+
+        multicast_a.close().await;
+        multicast_b.close().await;
+        core_a.close().await.ok();
+        core_b.close().await.ok();
+    }
+}
@@ -0,0 +1,213 @@
+//! Writing peers and listeners changed over the admin socket back to the
+//! config file.
+//!
+//! The file is edited with `toml_edit`, so comments, ordering and the
+//! formatting of everything else are kept. The new text is written to a
+//! temporary file next to the config, with the same permissions (it holds
+//! the private key), and renamed over it, so a crash never leaves a
+//! half-written config behind.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use toml_edit::{Array, DocumentMut, Item, Value};
+
+use crate::config::ConfigFormat;
+
+/// A change to one of the config's string lists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListEdit<'a> {
+    Add(&'a str),
+    Remove(&'a str),
+}
+
+/// Apply `edit` to the array `key` (`"peers"` or `"listen"`) of the TOML
+/// document in `text`. Returns the new text, or `None` when the list
+/// already was as requested.
+pub fn edit_list(text: &str, key: &str, edit: ListEdit) -> Result<Option<String>, String> {
+    let mut doc: DocumentMut = text.parse().map_err(|e| format!("{}", e))?;
+    if doc.get(key).is_none() {
+        doc.insert(key, Item::Value(Value::Array(Array::new())));
+    }
+    let array = doc[key]
+        .as_array_mut()
+        .ok_or_else(|| format!("'{}' is not an array", key))?;
+    if array.iter().any(|v| v.as_str().is_none()) {
+        return Err(format!("'{}' must only contain strings", key));
+    }
+
+    match edit {
+        ListEdit::Add(item) => {
+            if array.iter().any(|v| v.as_str() == Some(item)) {
+                return Ok(None);
+            }
+            push_like_siblings(array, item);
+        }
+        ListEdit::Remove(item) => {
+            let position = |array: &Array| array.iter().position(|v| v.as_str() == Some(item));
+            let Some(mut i) = position(array) else {
+                return Ok(None);
+            };
+            loop {
+                remove_line(array, i);
+                match position(array) {
+                    Some(next) => i = next,
+                    None => break,
+                }
+            }
+        }
+    }
+    Ok(Some(doc.to_string()))
+}
+
+/// Whitespace and comments before an array element. A comment at the end
+/// of an element's line belongs to the next element's prefix.
+fn prefix(value: &Value) -> &str {
+    value.decor().prefix().and_then(|p| p.as_str()).unwrap_or_default()
+}
+
+/// Append `item`, laid out like the last element: on its own line in a
+/// multi-line array, after a space in a one-line array.
+fn push_like_siblings(array: &mut Array, item: &str) {
+    let layout = match array.iter().last().map(prefix) {
+        None => String::new(),
+        // Keep the indentation, not the comments
+        Some(last) => match last.rfind('\n') {
+            Some(i) => format!("\n{}", &last[i + 1..]),
+            None => " ".to_string(),
+        },
+    };
+    let mut value = Value::from(item);
+    value.decor_mut().set_prefix(layout);
+    array.push_formatted(value);
+}
+
+/// Remove element `i` together with its line in a multi-line array: the
+/// comment lines above it and the comment after it.
+fn remove_line(array: &mut Array, i: usize) {
+    let removed = prefix(array.get(i).unwrap()).to_string();
+    array.remove(i);
+    // Everything up to the removed element's line stays
+    let keep = &removed[..removed.rfind('\n').map_or(0, |n| n + 1)];
+    match array.get_mut(i) {
+        Some(next) => {
+            let next_prefix = prefix(next).to_string();
+            let joined = match next_prefix.find('\n') {
+                Some(n) => format!("{}{}", keep, &next_prefix[n + 1..]),
+                None => removed.clone(),
+            };
+            next.decor_mut().set_prefix(joined);
+        }
+        None => {
+            let trailing = array.trailing().as_str().unwrap_or_default().to_string();
+            if let Some(n) = trailing.find('\n') {
+                array.set_trailing(format!("{}{}", keep, &trailing[n + 1..]));
+            }
+        }
+    }
+}
+
+/// Apply `edit` to the config file at `path`. Returns whether the file
+/// changed.
+pub fn update_file(path: &Path, key: &str, edit: ListEdit) -> Result<bool, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if ConfigFormat::detect(&text) == ConfigFormat::Go {
+        return Err(format!("{}: yggdrasil-go configs can't be updated, convert the file with --normaliseconf", path.display()));
+    }
+    let Some(text) = edit_list(&text, key, edit).map_err(|e| format!("{}: {}", path.display(), e))? else {
+        return Ok(false);
+    };
+    write_atomic(path, &text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(true)
+}
+
+/// Replace the file at `path` with `text` by renaming a temporary file
+/// over it.
+fn write_atomic(path: &Path, text: &str) -> std::io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file"))?;
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(name);
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+
+    let result = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.set_permissions(fs::metadata(path)?.permissions())?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"# My node
+private_key = "abc"
+
+# Peers to connect to
+peers = [
+  "tcp://192.0.2.1:9001",  # work
+  "tls://192.0.2.2:443",
+]
+
+listen = ["tcp://[::]:1234"]
+
+[node_info]
+name = "my-node"
+"#;
+
+    #[test]
+    fn test_edit_list() {
+        let added = edit_list(CONFIG, "peers", ListEdit::Add("quic://192.0.2.3:443")).unwrap().unwrap();
+        assert_eq!(
+            added,
+            CONFIG.replace(
+                "  \"tls://192.0.2.2:443\",\n",
+                "  \"tls://192.0.2.2:443\",\n  \"quic://192.0.2.3:443\",\n"
+            )
+        );
+        assert_eq!(edit_list(&added, "peers", ListEdit::Add("quic://192.0.2.3:443")).unwrap(), None);
+
+        let removed = edit_list(&added, "peers", ListEdit::Remove("tls://192.0.2.2:443")).unwrap().unwrap();
+        assert!(removed.contains("\"tcp://192.0.2.1:9001\",  # work\n"));
+        assert!(!removed.contains("tls://"));
+        assert!(removed.starts_with("# My node\n"));
+        assert_eq!(edit_list(&removed, "peers", ListEdit::Remove("tls://192.0.2.2:443")).unwrap(), None);
+
+        let listen = edit_list(CONFIG, "listen", ListEdit::Add("tls://[::]:443")).unwrap().unwrap();
+        assert!(listen.contains("listen = [\"tcp://[::]:1234\", \"tls://[::]:443\"]\n"));
+
+        // A missing list is created outside of any table
+        let bare = edit_list("[node_info]\nname = \"x\"\n", "peers", ListEdit::Add("tcp://192.0.2.1:9001")).unwrap().unwrap();
+        let config: crate::config::Config = toml::from_str(&bare).unwrap();
+        assert_eq!(config.peers, ["tcp://192.0.2.1:9001"]);
+        assert_eq!(config.node_info["name"].as_str(), Some("x"));
+
+        assert!(edit_list("peers = \"x\"\n", "peers", ListEdit::Add("y")).is_err());
+        assert!(edit_list("peers = [", "peers", ListEdit::Add("y")).is_err());
+    }
+
+    #[test]
+    fn test_update_file() {
+        let dir = std::env::temp_dir().join(format!("yggdrasil-persist-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("yggdrasil.toml");
+        fs::write(&path, CONFIG).unwrap();
+
+        assert!(update_file(&path, "peers", ListEdit::Remove("tcp://192.0.2.1:9001")).unwrap());
+        assert!(!update_file(&path, "peers", ListEdit::Remove("tcp://192.0.2.1:9001")).unwrap());
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("# Peers to connect to\npeers = [\n  \"tls://192.0.2.2:443\",\n]\n"), "{}", text);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,223 @@
+//! Session protocol packets (`TYPE_SESSION_PROTO`), compatible with
+//! yggdrasil-go: NodeInfo and remote debug requests and responses.
+//!
+//! A NodeInfo request is the single byte `TYPE_PROTO_NODEINFO_REQUEST`; the
+//! answer is `TYPE_PROTO_NODEINFO_RESPONSE` followed by the node's NodeInfo
+//! as a JSON object. Debug packets start with `TYPE_PROTO_DEBUG` and a debug
+//! type. GetSelf is answered with a small JSON object, GetPeers and GetTree
+//! with concatenated 32-byte public keys, cut off to fit the MTU.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+
+use tokio::sync::oneshot;
+
+pub(crate) const TYPE_PROTO_NODEINFO_REQUEST: u8 = 1;
+pub(crate) const TYPE_PROTO_NODEINFO_RESPONSE: u8 = 2;
+pub(crate) const TYPE_PROTO_DEBUG: u8 = 255;
+
+pub(crate) const TYPE_DEBUG_GET_SELF_REQUEST: u8 = 1;
+pub(crate) const TYPE_DEBUG_GET_SELF_RESPONSE: u8 = 2;
+pub(crate) const TYPE_DEBUG_GET_PEERS_REQUEST: u8 = 3;
+pub(crate) const TYPE_DEBUG_GET_PEERS_RESPONSE: u8 = 4;
+pub(crate) const TYPE_DEBUG_GET_TREE_REQUEST: u8 = 5;
+pub(crate) const TYPE_DEBUG_GET_TREE_RESPONSE: u8 = 6;
+
+/// Largest NodeInfo we are willing to send (same limit as yggdrasil-go).
+const MAX_NODEINFO_SIZE: usize = 16384;
+
+/// Build our NodeInfo JSON from the configured table, adding build details
+/// unless `privacy` is set.
+pub fn node_info_json(node_info: &toml::Value, privacy: bool) -> Result<Vec<u8>, String> {
+    let mut info = match serde_json::to_value(node_info) {
+        Ok(serde_json::Value::Object(map)) => map,
+        Ok(_) => return Err("node_info must be a table".to_string()),
+        Err(e) => return Err(format!("node_info: {}", e)),
+    };
+    if !privacy {
+        info.insert("buildname".to_string(), env!("CARGO_PKG_NAME").into());
+        info.insert("buildversion".to_string(), env!("CARGO_PKG_VERSION").into());
+        info.insert("buildplatform".to_string(), go_os().into());
+        info.insert("buildarch".to_string(), go_arch().into());
+    }
+    let json = serde_json::to_vec(&info).map_err(|e| format!("node_info: {}", e))?;
+    if json.len() > MAX_NODEINFO_SIZE {
+        return Err(format!("node_info exceeds max length of {} bytes", MAX_NODEINFO_SIZE));
+    }
+    Ok(json)
+}
+
+/// Operating system under the name yggdrasil-go reports (`GOOS`). Rust
+/// and Go agree on the rest.
+fn go_os() -> &'static str {
+    match std::env::consts::OS {
+        "macos" => "darwin",
+        other => other,
+    }
+}
+
+/// Architecture under the name yggdrasil-go reports (`GOARCH`), so network
+/// maps see one spelling for both implementations.
+fn go_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "x86" => "386",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64",
+        "loongarch64" => "loong64",
+        other => other,
+    }
+}
+
+/// Callers waiting for one kind of response, by remote key.
+#[derive(Default)]
+pub(crate) struct Waiters(Mutex<HashMap<[u8; 32], Vec<Waiter>>>);
+
+type Waiter = oneshot::Sender<Vec<u8>>;
+
+impl Waiters {
+    /// Register interest in the next response from `key`.
+    pub(crate) fn wait(&self, key: [u8; 32]) -> oneshot::Receiver<Vec<u8>> {
+        let (tx, rx) = oneshot::channel();
+        let mut waiters = self.0.lock().unwrap();
+        // Forget callers that gave up (timed out) in the meantime
+        waiters.retain(|_, list| {
+            list.retain(|w| !w.is_closed());
+            !list.is_empty()
+        });
+        waiters.entry(key).or_default().push(tx);
+        rx
+    }
+
+    /// Hand a response to everyone waiting for it. Unsolicited responses
+    /// are dropped.
+    pub(crate) fn deliver(&self, key: [u8; 32], data: &[u8]) {
+        let waiters = self.0.lock().unwrap().remove(&key);
+        for waiter in waiters.into_iter().flatten() {
+            let _ = waiter.send(data.to_vec());
+        }
+    }
+}
+
+/// Debug response carrying `keys`, as many as fit in `mtu`.
+pub(crate) fn debug_keys_response(response: u8, keys: impl IntoIterator<Item = [u8; 32]>, mtu: u64) -> Vec<u8> {
+    let mut out = vec![TYPE_PROTO_DEBUG, response];
+    for key in keys {
+        if (out.len() + key.len()) as u64 > mtu {
+            break;
+        }
+        out.extend_from_slice(&key);
+    }
+    out
+}
+
+/// Split a GetPeers or GetTree response into keys; a trailing partial key
+/// is ignored.
+pub(crate) fn parse_keys(data: &[u8]) -> Vec<[u8; 32]> {
+    data.chunks_exact(32)
+        .map(|chunk| chunk.try_into().unwrap())
+        .collect()
+}
+
+/// Our NodeInfo and the callers waiting for remote responses.
+pub(crate) struct ProtoHandler {
+    node_info: RwLock<Vec<u8>>,
+    pub(crate) node_info_waiters: Waiters,
+    pub(crate) self_waiters: Waiters,
+    pub(crate) peers_waiters: Waiters,
+    pub(crate) tree_waiters: Waiters,
+}
+
+impl ProtoHandler {
+    pub(crate) fn new(node_info: Vec<u8>) -> Self {
+        Self {
+            node_info: RwLock::new(node_info),
+            node_info_waiters: Waiters::default(),
+            self_waiters: Waiters::default(),
+            peers_waiters: Waiters::default(),
+            tree_waiters: Waiters::default(),
+        }
+    }
+
+    /// Callers waiting for the given debug response type.
+    pub(crate) fn debug_waiters(&self, response: u8) -> Option<&Waiters> {
+        match response {
+            TYPE_DEBUG_GET_SELF_RESPONSE => Some(&self.self_waiters),
+            TYPE_DEBUG_GET_PEERS_RESPONSE => Some(&self.peers_waiters),
+            TYPE_DEBUG_GET_TREE_RESPONSE => Some(&self.tree_waiters),
+            _ => None,
+        }
+    }
+
+    /// Our NodeInfo JSON.
+    pub(crate) fn node_info(&self) -> Vec<u8> {
+        self.node_info.read().unwrap().clone()
+    }
+
+    /// Replace our NodeInfo (config reload).
+    pub(crate) fn set_node_info(&self, node_info: Vec<u8>) {
+        *self.node_info.write().unwrap() = node_info;
+    }
+
+    /// Response packet (without the session type byte) to a NodeInfo request.
+    pub(crate) fn node_info_response(&self) -> Vec<u8> {
+        let node_info = self.node_info.read().unwrap();
+        let mut out = Vec::with_capacity(1 + node_info.len());
+        out.push(TYPE_PROTO_NODEINFO_RESPONSE);
+        out.extend_from_slice(&node_info);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_info_json() {
+        let table: toml::Value = toml::from_str("name = \"my-node\"\nlocation = \"earth\"").unwrap();
+
+        let info: serde_json::Value = serde_json::from_slice(&node_info_json(&table, false).unwrap()).unwrap();
+        assert_eq!(info["name"], "my-node");
+        assert_eq!(info["buildname"], env!("CARGO_PKG_NAME"));
+        assert_eq!(info["buildversion"], env!("CARGO_PKG_VERSION"));
+        assert!(info["buildplatform"].is_string());
+        assert_ne!(info["buildplatform"], "macos");
+
+        let info: serde_json::Value = serde_json::from_slice(&node_info_json(&table, true).unwrap()).unwrap();
+        assert_eq!(info, serde_json::json!({ "name": "my-node", "location": "earth" }));
+
+        let huge = toml::Value::Table([("blob".to_string(), toml::Value::String("x".repeat(20000)))].into_iter().collect());
+        assert!(node_info_json(&huge, true).is_err());
+        assert!(node_info_json(&toml::Value::Integer(1), true).is_err());
+    }
+
+    #[test]
+    fn test_debug_keys() {
+        let keys = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        let packet = debug_keys_response(TYPE_DEBUG_GET_PEERS_RESPONSE, keys, 2 + 64);
+        assert_eq!(packet.len(), 2 + 64);
+        assert_eq!(&packet[..2], &[TYPE_PROTO_DEBUG, TYPE_DEBUG_GET_PEERS_RESPONSE]);
+        assert_eq!(parse_keys(&packet[2..]), keys[..2]);
+        assert_eq!(parse_keys(&[7u8; 40]), [[7u8; 32]]);
+    }
+
+    #[tokio::test]
+    async fn test_waiters() {
+        let handler = ProtoHandler::new(b"{}".to_vec());
+        assert_eq!(handler.node_info_response(), [TYPE_PROTO_NODEINFO_RESPONSE, b'{', b'}']);
+        handler.set_node_info(b"[]".to_vec());
+        assert_eq!(handler.node_info_response(), [TYPE_PROTO_NODEINFO_RESPONSE, b'[', b']']);
+
+        let waiters = Waiters::default();
+        let a = waiters.wait([1; 32]);
+        let b = waiters.wait([1; 32]);
+        drop(waiters.wait([2; 32]));
+        waiters.deliver([1; 32], b"{\"x\":1}");
+        assert_eq!(a.await.unwrap(), b"{\"x\":1}");
+        assert_eq!(b.await.unwrap(), b"{\"x\":1}");
+        // The abandoned waiter is cleaned up by the next registration
+        let _c = waiters.wait([3; 32]);
+        assert_eq!(waiters.0.lock().unwrap().len(), 1);
+    }
+}
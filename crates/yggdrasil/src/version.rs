@@ -182,7 +182,7 @@ impl Metadata {
 }
 
 /// Compute BLAKE2b-512 hash of data, optionally keyed with password.
-fn blake2b_hash(data: &[u8], password: &[u8]) -> [u8; 64] {
+pub(crate) fn blake2b_hash(data: &[u8], password: &[u8]) -> [u8; 64] {
     if password.is_empty() {
         // Unkeyed: Go's blake2b.New512(nil) → use as a keyed MAC with empty key?
         // Actually, Go's blake2b.New512(nil) creates an unkeyed hash.
@@ -1,15 +1,23 @@
 use comfy_table::{presets, Table};
 use getopts::Options;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use time::macros::format_description;
+use time::{OffsetDateTime, UtcOffset};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
+use yggdrasil::address::addr_for_key;
+use yggdrasil::admin::AdminSignature;
+use yggdrasil::config::parse_private_key;
+use yggdrasil::crawl::Graph;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = std::env::args().collect();
 
     let mut opts = Options::new();
-    opts.optopt("e", "endpoint", "Admin socket address (default: tcp://localhost:9001)", "URI");
+    opts.optopt("e", "endpoint", "Admin socket address, tcp://host:port or unix:///path (default: tcp://localhost:9001)", "URI");
     opts.optflag("j", "json", "Output as raw JSON");
+    opts.optopt("", "token-file", "Read the admin token from FILE (default: $YGGDRASIL_ADMIN_TOKEN)", "FILE");
+    opts.optopt("", "key-file", "Sign requests with the hex Ed25519 private key in FILE", "FILE");
     opts.optflag("h", "help", "Print this help");
     opts.optflag("v", "version", "Print version");
 
@@ -24,7 +32,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     if matches.opt_present("help") {
         println!("{}", opts.usage("Usage: yggdrasilctl [options] <command> [key=value ...]"));
-        println!("Commands: list, getSelf, getPeers, getTree, addPeer, removePeer");
+        println!("Commands: list, getSelf, getPeers, getTree, getPaths, getSessions, getNodeInfo, debug_remoteGetSelf, debug_remoteGetPeers, debug_remoteGetTree, crawl, watch, addPeer, removePeer, addListener, removeListener, reloadConfig");
         return Ok(());
     }
 
@@ -41,7 +49,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some(c) => c.clone(),
         None => {
             eprintln!("Usage: yggdrasilctl [options] <command> [key=value ...]");
-            eprintln!("Commands: list, getSelf, getPeers, getTree, addPeer, removePeer");
+            eprintln!("Commands: list, getSelf, getPeers, getTree, getPaths, getSessions, getNodeInfo, debug_remoteGetSelf, debug_remoteGetPeers, debug_remoteGetTree, crawl, watch, addPeer, removePeer, addListener, removeListener, reloadConfig");
             std::process::exit(1);
         }
     };
@@ -54,24 +62,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    let request = serde_json::json!({
-        "request": command,
+    // The crawl output format is ours to handle, not the daemon's
+    let mut format = "json".to_string();
+    if command.eq_ignore_ascii_case("crawl") {
+        if let Some(f) = arguments.remove("format").and_then(|v| v.as_str().map(str::to_lowercase)) {
+            format = f;
+        }
+    }
+    if !matches!(format.as_str(), "json" | "dot" | "graphml") {
+        eprintln!("Unknown format '{}', expected json, dot or graphml", format);
+        std::process::exit(1);
+    }
+
+    // watch is a subscribe request whose answer is followed by events
+    let watching = command.eq_ignore_ascii_case("watch");
+    let request_name = if watching { "subscribe" } else { command.as_str() };
+    let arguments = serde_json::Value::Object(arguments);
+    let mut request = serde_json::json!({
+        "request": request_name,
         "arguments": arguments,
         "keepalive": false,
     });
+    if let Some(path) = matches.opt_str("key-file") {
+        let key = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read key file {}: {}", path, e))
+            .and_then(|hex_key| parse_private_key(&hex_key))?;
+        request["auth"] = serde_json::to_value(AdminSignature::sign(&key, request_name, &arguments))?;
+    } else if let Some(token) = admin_token(matches.opt_str("token-file"))? {
+        request["token"] = token.into();
+    }
 
-    let addr = endpoint
-        .strip_prefix("tcp://")
-        .unwrap_or(&endpoint);
-
-    let stream = TcpStream::connect(addr).await.map_err(|e| {
+    let (reader, mut writer) = connect(&endpoint).await.map_err(|e| {
         format!(
             "Failed to connect to admin socket at {}: {}",
             endpoint, e
         )
     })?;
-
-    let (reader, mut writer) = stream.into_split();
     let mut reader = BufReader::new(reader);
 
     // Send request
@@ -91,6 +117,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let resp: serde_json::Value = serde_json::from_str(line.trim())?;
 
+    if watching && resp.get("status").and_then(|v| v.as_str()) == Some("success") {
+        return watch(reader, json_output).await;
+    }
+
     if json_output {
         println!("{}", serde_json::to_string_pretty(&resp)?);
         return Ok(());
@@ -146,8 +176,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     let mut table = Table::new();
                     table.load_preset(presets::NOTHING);
                     table.set_header(vec![
-                        "URI", "State", "Dir", "IP Address", "Uptime", "RX", "TX",
-                        "RX Rate", "TX Rate", "Pr", "Last Error"
+                        "URI", "State", "Dir", "IP Address", "Port", "RTT", "Pref", "Uptime", "Retry",
+                        "RX", "TX", "RX Rate", "TX Rate", "Pr", "Last Error"
                     ]);
 
                     for peer in peers {
@@ -157,7 +187,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         let up = peer.get("up")
                             .and_then(|v| v.as_bool())
                             .unwrap_or(false);
-                        let state = if up { "Up" } else { "Down" };
+                        let state = match peer.get("state").and_then(|v| v.as_str()) {
+                            Some("connecting") => "Connecting",
+                            Some("handshaking") => "Handshaking",
+                            Some("backoff") => "Backoff",
+                            _ if up => "Up",
+                            _ => "Down",
+                        };
+                        let retry = peer.get("next_retry")
+                            .and_then(|v| v.as_f64())
+                            .map(|secs| {
+                                let attempts = peer.get("attempts").and_then(|v| v.as_u64()).unwrap_or(0);
+                                format!("{} (#{})", format_uptime(secs), attempts)
+                            })
+                            .unwrap_or_else(|| "-".to_string());
                         let inbound = peer.get("inbound")
                             .and_then(|v| v.as_bool())
                             .unwrap_or(false);
@@ -167,6 +210,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             .unwrap_or("-");
                         let uptime = peer.get("uptime")
                             .and_then(|v| v.as_f64())
+                            .filter(|_| up)
                             .map(format_uptime)
                             .unwrap_or_else(|| "-".to_string());
                         let rx_bytes = peer.get("bytes_recvd")
@@ -185,6 +229,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             .and_then(|v| v.as_u64())
                             .map(|r| if r > 0 { format!("{}/s", format_bytes(r)) } else { "-".to_string() })
                             .unwrap_or_else(|| "-".to_string());
+                        let port = peer.get("port")
+                            .and_then(|v| v.as_u64())
+                            .map(|p| p.to_string())
+                            .unwrap_or_else(|| "-".to_string());
+                        let rtt = peer.get("latency_ms")
+                            .and_then(|v| v.as_f64())
+                            .map(|ms| format!("{:.1}ms", ms))
+                            .unwrap_or_else(|| "-".to_string());
+                        let preferred = peer.get("preferred")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        let preferred = if preferred { "*" } else { "" };
                         let priority = peer.get("priority")
                             .and_then(|v| v.as_u64())
                             .map(|p| p.to_string())
@@ -195,7 +251,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             .unwrap_or("-");
 
                         table.add_row(vec![
-                            uri, state, dir, address, &uptime, &rx_bytes, &tx_bytes,
+                            uri, state, dir, address, &port, &rtt, preferred, &uptime, &retry, &rx_bytes, &tx_bytes,
                             &rx_rate, &tx_rate, &priority, last_error
                         ]);
                     }
@@ -237,6 +293,114 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
+        "getpaths" => {
+            if let Some(paths) = response.get("paths").and_then(|v| v.as_array()) {
+                if paths.is_empty() {
+                    println!("No paths.");
+                } else {
+                    let mut table = Table::new();
+                    table.load_preset(presets::NOTHING);
+                    table.set_header(vec!["IP Address", "State", "Path", "Sequence", "Age", "Throttled"]);
+
+                    for entry in paths {
+                        let address = entry.get("address")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("-");
+                        let flag = |name: &str| entry.get(name).and_then(|v| v.as_bool()).unwrap_or(false);
+                        let path = entry.get("path")
+                            .and_then(|v| v.as_array())
+                            .filter(|p| !p.is_empty())
+                            .map(|p| format!("{:?}", p.iter().filter_map(|v| v.as_u64()).collect::<Vec<_>>()))
+                            .unwrap_or_else(|| "-".to_string());
+                        let state = if flag("broken") {
+                            "Broken"
+                        } else if path == "-" {
+                            "Looking up"
+                        } else {
+                            "Known"
+                        };
+                        let sequence = entry.get("sequence")
+                            .and_then(|v| v.as_u64())
+                            .filter(|_| path != "-")
+                            .map(|s| s.to_string())
+                            .unwrap_or_else(|| "-".to_string());
+                        let age = entry.get("age")
+                            .and_then(|v| v.as_f64())
+                            .map(format_uptime)
+                            .unwrap_or_else(|| "-".to_string());
+                        let throttled = if flag("throttled") { "Yes" } else { "-" };
+
+                        table.add_row(vec![address, state, &path, &sequence, &age, throttled]);
+                    }
+
+                    println!("{}", table);
+                }
+            }
+        }
+
+        "getsessions" => {
+            if let Some(sessions) = response.get("sessions").and_then(|v| v.as_array()) {
+                if sessions.is_empty() {
+                    println!("No sessions.");
+                } else {
+                    let mut table = Table::new();
+                    table.load_preset(presets::NOTHING);
+                    table.set_header(vec![
+                        "IP Address", "State", "Uptime", "RX", "TX", "Last Rekey", "Idle", "Buffered"
+                    ]);
+
+                    for session in sessions {
+                        let address = session.get("address")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("-");
+                        let established = session.get("established")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        let state = if established { "Up" } else { "Handshake" };
+                        let uptime = session.get("uptime")
+                            .and_then(|v| v.as_f64())
+                            .map(format_uptime)
+                            .unwrap_or_else(|| "-".to_string());
+                        let rx_bytes = session.get("bytes_recvd")
+                            .and_then(|v| v.as_u64())
+                            .map(format_bytes)
+                            .unwrap_or_else(|| "-".to_string());
+                        let tx_bytes = session.get("bytes_sent")
+                            .and_then(|v| v.as_u64())
+                            .map(format_bytes)
+                            .unwrap_or_else(|| "-".to_string());
+                        let last_rekey = session.get("last_rekey")
+                            .and_then(|v| v.as_f64())
+                            .map(|s| format!("{} ago", format_uptime(s)))
+                            .unwrap_or_else(|| "-".to_string());
+                        let idle = session.get("last_activity")
+                            .and_then(|v| v.as_f64())
+                            .map(format_uptime)
+                            .unwrap_or_else(|| "-".to_string());
+                        let buffered = session.get("buffered")
+                            .and_then(|v| v.as_bool())
+                            .unwrap_or(false);
+                        let buffered = if buffered { "Yes" } else { "-" };
+
+                        table.add_row(vec![
+                            address, state, &uptime, &rx_bytes, &tx_bytes, &last_rekey, &idle, buffered
+                        ]);
+                    }
+
+                    println!("{}", table);
+                }
+            }
+        }
+
+        "crawl" => {
+            let graph: Graph = serde_json::from_value(response.clone())?;
+            match format.as_str() {
+                "dot" => print!("{}", graph.to_dot()),
+                "graphml" => print!("{}", graph.to_graphml()),
+                _ => println!("{}", serde_json::to_string_pretty(&graph)?),
+            }
+        }
+
         _ => {
             // Generic: print the response as pretty JSON
             println!("{}", serde_json::to_string_pretty(response)?);
@@ -246,6 +410,105 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// The admin token from `path`, or else from the environment.
+fn admin_token(path: Option<String>) -> Result<Option<String>, String> {
+    let token = match path {
+        Some(path) => std::fs::read_to_string(&path).map_err(|e| format!("Failed to read token file {}: {}", path, e))?,
+        None => match std::env::var("YGGDRASIL_ADMIN_TOKEN") {
+            Ok(token) => token,
+            Err(_) => return Ok(None),
+        },
+    };
+    let token = token.trim();
+    Ok((!token.is_empty()).then(|| token.to_string()))
+}
+
+type Reader = Box<dyn AsyncRead + Unpin + Send>;
+type Writer = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// Connect to the admin socket at a `tcp://` or `unix://` endpoint (a bare
+/// "host:port" is taken as TCP).
+async fn connect(endpoint: &str) -> std::io::Result<(Reader, Writer)> {
+    if let Some(path) = endpoint.strip_prefix("unix://") {
+        #[cfg(unix)]
+        {
+            let (reader, writer) = tokio::net::UnixStream::connect(path).await?.into_split();
+            return Ok((Box::new(reader), Box::new(writer)));
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = path;
+            return Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "unix sockets are not supported on this platform"));
+        }
+    }
+    let addr = endpoint.strip_prefix("tcp://").unwrap_or(endpoint);
+    let (reader, writer) = TcpStream::connect(addr).await?.into_split();
+    Ok((Box::new(reader), Box::new(writer)))
+}
+
+/// Print events from a `subscribe` stream as they arrive, until the
+/// daemon closes the connection.
+async fn watch(mut reader: BufReader<Reader>, json_output: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if json_output {
+            println!("{}", line);
+            continue;
+        }
+        let event: serde_json::Value = serde_json::from_str(line)?;
+        let time = event
+            .get("time")
+            .and_then(|v| v.as_f64())
+            .and_then(|t| OffsetDateTime::from_unix_timestamp(t as i64).ok())
+            .and_then(|t| t.to_offset(offset).format(format_description!("[hour]:[minute]:[second]")).ok())
+            .unwrap_or_else(|| "--:--:--".to_string());
+        println!("{}  {}", time, describe_event(&event));
+    }
+}
+
+/// One line describing an event from the `subscribe` stream.
+fn describe_event(event: &serde_json::Value) -> String {
+    let field = |name: &str| event.get(name).and_then(|v| v.as_str()).unwrap_or("-");
+    let seconds = |name: &str| event.get(name).and_then(|v| v.as_f64()).map(format_uptime).unwrap_or_else(|| "-".to_string());
+    // Tree events carry keys only
+    let address = |name: &str| {
+        hex::decode(field(name))
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .map(|key| addr_for_key(&key).to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let dir = if event.get("inbound").and_then(|v| v.as_bool()).unwrap_or(false) { "inbound" } else { "outbound" };
+    let remote = event.get("remote").and_then(|v| v.as_str()).unwrap_or_else(|| field("uri"));
+    match field("event") {
+        "link_connected" => format!("Link up: {} @ {} ({})", field("address"), remote, dir),
+        "link_disconnected" => format!(
+            "Link down: {} @ {} ({}, up {}): {}",
+            field("address"),
+            remote,
+            dir,
+            seconds("uptime"),
+            field("reason")
+        ),
+        "ban_added" => format!("Banned {} for {}: {}", field("ip"), seconds("duration"), field("reason")),
+        "tree_changed" => format!("Tree: root {}, parent {}", address("root"), address("parent")),
+        "path_notify" => format!("Path learned: {}", field("address")),
+        "session_established" => format!("Session established: {}", field("address")),
+        "session_expired" => format!("Session expired: {}", field("address")),
+        "lagged" => format!("Missed {} events", event.get("missed").and_then(|v| v.as_u64()).unwrap_or(0)),
+        _ => event.to_string(),
+    }
+}
+
 fn print_kv(obj: &serde_json::Value, fields: &[(&str, &str)]) {
     let max_label = fields.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
     for (label, key) in fields {